@@ -0,0 +1,59 @@
+use eframe::egui::{Align, Layout, Pos2, Rect, Vec2};
+
+#[derive(Clone, Copy, Eq, PartialEq)]
+pub enum Anchor {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+    Center,
+}
+
+impl Anchor {
+    pub fn parse(s: &str) -> Option<Anchor> {
+        let ret = match s {
+            "top-left" => Anchor::TopLeft,
+            "top-right" => Anchor::TopRight,
+            "bottom-left" => Anchor::BottomLeft,
+            "bottom-right" => Anchor::BottomRight,
+            "center" => Anchor::Center,
+            _ => return None,
+        };
+
+        Some(ret)
+    }
+
+    // Where the window's top left corner goes to sit in the anchored corner of the monitor
+    pub fn window_position(&self, monitor: Rect, window_size: Vec2, margin: f32) -> Pos2 {
+        let left = monitor.left() + margin;
+        let right = monitor.right() - margin - window_size.x;
+        let top = monitor.top() + margin;
+        let bottom = monitor.bottom() - margin - window_size.y;
+
+        match self {
+            Anchor::TopLeft => Pos2::new(left, top),
+            Anchor::TopRight => Pos2::new(right, top),
+            Anchor::BottomLeft => Pos2::new(left, bottom),
+            Anchor::BottomRight => Pos2::new(right, bottom),
+            Anchor::Center => monitor.center() - window_size / 2.0,
+        }
+    }
+
+    // The newest key sits at the anchored edge and older ones move away from it
+    pub fn grows_down(&self) -> bool {
+        matches!(self, Anchor::TopLeft | Anchor::TopRight)
+    }
+
+    pub fn layout(&self) -> Layout {
+        let align = match self {
+            Anchor::TopLeft | Anchor::BottomLeft => Align::Min,
+            Anchor::TopRight | Anchor::BottomRight => Align::Max,
+            Anchor::Center => Align::Center,
+        };
+
+        match self.grows_down() {
+            true => Layout::top_down(align),
+            false => Layout::bottom_up(align),
+        }
+    }
+}
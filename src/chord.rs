@@ -0,0 +1,121 @@
+use super::{KeyHistoryItem, Modifiers};
+
+// A user specified key chord, e.g. "Ctrl+Shift+z"
+#[derive(Debug)]
+pub struct Chord {
+    modifiers: Modifiers,
+    key: String,
+}
+
+impl Chord {
+    pub fn parse(s: &str) -> Option<Chord> {
+        let (modifier_s, key) = match s.strip_suffix("++") {
+            Some(m) => (m, "+"),
+            None => s.rsplit_once('+').unwrap_or(("", s)),
+        };
+
+        let key = key.trim();
+        if key.is_empty() {
+            return None;
+        }
+
+        let mut modifiers = Modifiers::default();
+        for modifier in modifier_s.split('+').filter(|m| !m.trim().is_empty()) {
+            match modifier.trim().to_lowercase().as_str() {
+                "ctrl" | "control" => modifiers.ctrl = true,
+                "shift" => modifiers.shift = true,
+                "alt" => modifiers.alt = true,
+                "super" | "meta" => modifiers.sup = true,
+                _ => return None,
+            }
+        }
+
+        Some(Chord {
+            modifiers,
+            key: key.to_string(),
+        })
+    }
+
+    pub fn matches(&self, item: &KeyHistoryItem) -> bool {
+        self.matches_key(&item.key_s, &item.modifiers)
+    }
+
+    pub fn matches_key(&self, key_s: &str, modifiers: &Modifiers) -> bool {
+        // Shift changes the reported case of the key, users shouldn't have to care
+        self.modifiers == *modifiers && self.key.to_lowercase() == key_s.to_lowercase()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn modifiers(ctrl: bool, shift: bool, alt: bool, sup: bool) -> Modifiers {
+        Modifiers {
+            ctrl,
+            shift,
+            alt,
+            sup,
+        }
+    }
+
+    #[test]
+    fn parse_modifiers_and_key() {
+        let chord = Chord::parse("Ctrl+Shift+z").unwrap();
+        assert_eq!(chord.modifiers, modifiers(true, true, false, false));
+        assert_eq!(chord.key, "z");
+
+        let chord = Chord::parse("control+META+alt+Return").unwrap();
+        assert_eq!(chord.modifiers, modifiers(true, false, true, true));
+        assert_eq!(chord.key, "Return");
+    }
+
+    #[test]
+    fn parse_bare_key() {
+        let chord = Chord::parse("F13").unwrap();
+        assert_eq!(chord.modifiers, Modifiers::default());
+        assert_eq!(chord.key, "F13");
+    }
+
+    #[test]
+    fn parse_plus_key() {
+        let chord = Chord::parse("Ctrl++").unwrap();
+        assert_eq!(chord.modifiers, modifiers(true, false, false, false));
+        assert_eq!(chord.key, "+");
+    }
+
+    #[test]
+    fn parse_trims_whitespace() {
+        let chord = Chord::parse(" Ctrl + c ").unwrap();
+        assert_eq!(chord.modifiers, modifiers(true, false, false, false));
+        assert_eq!(chord.key, "c");
+    }
+
+    #[test]
+    fn parse_rejects_invalid() {
+        for s in ["", " ", "+", "Ctrl+", "Ctrl+ ", "Hyper+a", "Ctrl+Foo+a"] {
+            assert!(Chord::parse(s).is_none(), "{s:?}");
+        }
+    }
+
+    #[test]
+    fn matches_ignores_key_case() {
+        let chord = Chord::parse("Ctrl+Shift+z").unwrap();
+        let ctrl_shift = modifiers(true, true, false, false);
+        assert!(chord.matches_key("Z", &ctrl_shift));
+        assert!(chord.matches_key("z", &ctrl_shift));
+        assert!(!chord.matches_key("x", &ctrl_shift));
+    }
+
+    #[test]
+    fn matches_needs_exact_modifiers() {
+        let chord = Chord::parse("Ctrl+c").unwrap();
+        assert!(chord.matches_key("c", &modifiers(true, false, false, false)));
+        assert!(!chord.matches_key("c", &modifiers(true, true, false, false)));
+        assert!(!chord.matches_key("c", &Modifiers::default()));
+
+        let chord = Chord::parse("c").unwrap();
+        assert!(chord.matches_key("c", &Modifiers::default()));
+        assert!(!chord.matches_key("c", &modifiers(false, false, true, false)));
+    }
+}
@@ -0,0 +1,41 @@
+use std::{collections::BTreeMap, time::Duration};
+
+// Shortcuts used fewer times than this are left out of the report, a single slow press says
+// little about the binding
+const MIN_USES: u32 = 3;
+const REPORT_LINES: usize = 10;
+
+#[derive(Default)]
+struct Timing {
+    uses: u32,
+    total: Duration,
+}
+
+// How long chords take from the first modifier to the key, and key sequences from the first key
+// to the last. Slow shortcuts that are used a lot are worth rebinding
+#[derive(Default)]
+pub struct ChordTimings {
+    // By chord text, e.g. "Ctrl + Shift + t"
+    timings: BTreeMap<String, Timing>,
+}
+
+impl ChordTimings {
+    pub fn record(&mut self, chord: String, took: Duration) {
+        let timing = self.timings.entry(chord).or_default();
+        timing.uses += 1;
+        timing.total += took;
+    }
+
+    // Slowest first, with how often they were used and their average time
+    pub fn slowest(&self) -> Vec<(&str, u32, Duration)> {
+        let mut ret = self
+            .timings
+            .iter()
+            .filter(|(_, t)| t.uses >= MIN_USES)
+            .map(|(chord, t)| (chord.as_str(), t.uses, t.total / t.uses))
+            .collect::<Vec<_>>();
+        ret.sort_by_key(|(_, _, average)| std::cmp::Reverse(*average));
+        ret.truncate(REPORT_LINES);
+        ret
+    }
+}
@@ -0,0 +1,147 @@
+use std::{
+    sync::mpsc::{self, Receiver, Sender},
+    thread,
+    time::Instant,
+};
+
+use eframe::egui;
+use x11rb::{
+    connection::Connection,
+    errors::{ConnectError, ConnectionError, ReplyError, ReplyOrIdError},
+    protocol::{
+        xproto::{Atom, AtomEnum, ConnectionExt as _, CreateWindowAux, Window, WindowClass},
+        Event,
+    },
+    rust_connection::RustConnection,
+};
+
+#[derive(Debug)]
+pub enum ClipboardError {
+    Connect(ConnectError),
+    Connection(ConnectionError),
+    Reply(ReplyError),
+    Id(ReplyOrIdError),
+}
+
+// Requests go out with the time of the paste, answers come back with it and the character count
+pub type PasteChannels = (Sender<Instant>, Receiver<(Instant, usize)>);
+
+struct Atoms {
+    clipboard: Atom,
+    utf8_string: Atom,
+    // Where the selection owner puts the contents for us
+    property: Atom,
+}
+
+fn intern(conn: &RustConnection, name: &[u8]) -> Result<Atom, ClipboardError> {
+    let atom = conn
+        .intern_atom(false, name)
+        .map_err(ClipboardError::Connection)?
+        .reply()
+        .map_err(ClipboardError::Reply)?
+        .atom;
+
+    Ok(atom)
+}
+
+// Answers requests with the number of characters on the X11 clipboard. Requests are sent when a
+// paste chord is pressed, so the answer is what was just pasted
+pub fn spawn_reader(rx: Receiver<egui::Context>) -> Result<PasteChannels, ClipboardError> {
+    let (conn, screen_num) = x11rb::connect(None).map_err(ClipboardError::Connect)?;
+    let root = conn.setup().roots[screen_num].root;
+
+    // Selection owners send the contents to a window, an invisible one is enough
+    let window = conn.generate_id().map_err(ClipboardError::Id)?;
+    conn.create_window(
+        x11rb::COPY_DEPTH_FROM_PARENT,
+        window,
+        root,
+        0,
+        0,
+        1,
+        1,
+        0,
+        WindowClass::INPUT_ONLY,
+        x11rb::COPY_FROM_PARENT,
+        &CreateWindowAux::new(),
+    )
+    .map_err(ClipboardError::Connection)?
+    .check()
+    .map_err(ClipboardError::Reply)?;
+
+    let atoms = Atoms {
+        clipboard: intern(&conn, b"CLIPBOARD")?,
+        utf8_string: intern(&conn, b"UTF8_STRING")?,
+        property: intern(&conn, b"KEYBOARD_OVERLAY_CLIPBOARD")?,
+    };
+
+    let (request_tx, request_rx) = mpsc::channel();
+    let (tx, app_rx) = mpsc::channel();
+    thread::spawn(move || reader_thread(conn, window, atoms, request_rx, tx, rx));
+    Ok((request_tx, app_rx))
+}
+
+fn reader_thread(
+    conn: RustConnection,
+    window: Window,
+    atoms: Atoms,
+    request_rx: Receiver<Instant>,
+    tx: Sender<(Instant, usize)>,
+    rx: Receiver<egui::Context>,
+) {
+    let ctx = rx.recv().unwrap();
+
+    for pasted_at in request_rx {
+        match clipboard_chars(&conn, window, &atoms) {
+            Ok(Some(chars)) => {
+                if tx.send((pasted_at, chars)).is_err() {
+                    return;
+                }
+                ctx.request_repaint();
+            }
+            Ok(None) => (),
+            Err(_) => return,
+        }
+    }
+}
+
+fn clipboard_chars(
+    conn: &RustConnection,
+    window: Window,
+    atoms: &Atoms,
+) -> Result<Option<usize>, ClipboardError> {
+    conn.convert_selection(
+        window,
+        atoms.clipboard,
+        atoms.utf8_string,
+        atoms.property,
+        x11rb::CURRENT_TIME,
+    )
+    .map_err(ClipboardError::Connection)?;
+    conn.flush().map_err(ClipboardError::Connection)?;
+
+    let notify = loop {
+        match conn.wait_for_event().map_err(ClipboardError::Connection)? {
+            Event::SelectionNotify(e) if e.requestor == window => break e,
+            _ => (),
+        }
+    };
+
+    // Nobody owns the clipboard, or the owner can't provide text
+    if notify.property == x11rb::NONE {
+        return Ok(None);
+    }
+
+    let reply = conn
+        .get_property(true, window, atoms.property, AtomEnum::ANY, 0, u32::MAX)
+        .map_err(ClipboardError::Connection)?
+        .reply()
+        .map_err(ClipboardError::Reply)?;
+
+    // Very large selections are sent in INCR chunks, those aren't followed
+    if reply.type_ != atoms.utf8_string {
+        return Ok(None);
+    }
+
+    Ok(Some(String::from_utf8_lossy(&reply.value).chars().count()))
+}
@@ -0,0 +1,48 @@
+use std::time::{Duration, Instant};
+
+use super::now_timestamp;
+
+// Where the app takes the current time from. --export-frames steps through a recording at its own
+// pace, so there the time is the recording's, moved forward one event or frame at a time
+pub struct Clock {
+    // Instant and timestamp the recording started at, and how far into it we are
+    recording: Option<(Instant, Duration, Duration)>,
+}
+
+impl Clock {
+    pub fn real() -> Clock {
+        Clock { recording: None }
+    }
+
+    pub fn recording() -> Clock {
+        Clock {
+            recording: Some((Instant::now(), now_timestamp(), Duration::ZERO)),
+        }
+    }
+
+    // Does nothing for the real clock
+    pub fn set_offset(&mut self, offset: Duration) {
+        if let Some((_, _, v)) = &mut self.recording {
+            *v = offset;
+        }
+    }
+
+    pub fn now(&self) -> Instant {
+        match self.recording {
+            Some((start, _, offset)) => start + offset,
+            None => Instant::now(),
+        }
+    }
+
+    // Same clock as event timestamps
+    pub fn timestamp(&self) -> Duration {
+        match self.recording {
+            Some((_, start, offset)) => start + offset,
+            None => now_timestamp(),
+        }
+    }
+
+    pub fn since(&self, earlier: Instant) -> Duration {
+        self.now().saturating_duration_since(earlier)
+    }
+}
@@ -0,0 +1,39 @@
+use std::env;
+
+use x11rb::{
+    errors::{ConnectError, ConnectionError, ReplyError},
+    protocol::xproto::ConnectionExt as _,
+};
+
+#[derive(Debug)]
+pub enum CompositorError {
+    Connect(ConnectError),
+    Connection(ConnectionError),
+    Reply(ReplyError),
+}
+
+// Without a compositing manager X11 has nothing to blend a transparent window with, and the
+// overlay shows up as a black box. Compositing managers own the _NET_WM_CM_Sn selection for
+// the screen they manage. Wayland compositors always composite
+pub fn is_running() -> Result<bool, CompositorError> {
+    if env::var_os("WAYLAND_DISPLAY").is_some() {
+        return Ok(true);
+    }
+
+    let (conn, screen_num) = x11rb::connect(None).map_err(CompositorError::Connect)?;
+    let selection = conn
+        .intern_atom(false, format!("_NET_WM_CM_S{screen_num}").as_bytes())
+        .map_err(CompositorError::Connection)?
+        .reply()
+        .map_err(CompositorError::Reply)?
+        .atom;
+
+    let owner = conn
+        .get_selection_owner(selection)
+        .map_err(CompositorError::Connection)?
+        .reply()
+        .map_err(CompositorError::Reply)?
+        .owner;
+
+    Ok(owner != x11rb::NONE)
+}
@@ -0,0 +1,352 @@
+use std::{
+    env, fs,
+    io::Error as IoError,
+    path::{Path, PathBuf},
+};
+
+use toml_edit::{Document, Item, Table, Value};
+
+// An option with its value, if it takes one, e.g. ["--font-size", "18"]
+type ConfigArg = Vec<String>;
+
+// Options that replace each other, setting one on the command line drops the others from the
+// config
+const EXCLUSIVE_OPTIONS: &[&[&str]] = &[
+    &[
+        "--event-input-path",
+        "--keyboard-devices",
+        "--replay",
+        "--backend",
+    ],
+    &["--theme", "--theme-preset"],
+    &["--text-color", "--theme-text"],
+];
+
+// Options that aren't the --no- form of another one
+const NO_OPTIONS: &[&str] = &["--no-persist"];
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Read(IoError),
+    Parse(toml_edit::TomlError),
+    InvalidValue(String),
+}
+
+fn default_path() -> Option<PathBuf> {
+    let config_dir = match env::var_os("XDG_CONFIG_HOME") {
+        Some(v) => PathBuf::from(v),
+        None => PathBuf::from(env::var_os("HOME")?).join(".config"),
+    };
+
+    Some(config_dir.join("keyboard-overlay").join("config.toml"))
+}
+
+fn value_args(key: &str, value: &Value, args: &mut Vec<ConfigArg>) -> Result<(), ConfigError> {
+    // Keys may be written with underscores, e.g. reduced_motion
+    let flag = format!("--{}", key.replace('_', "-"));
+    match value {
+        Value::String(s) => args.push(vec![flag, s.value().clone()]),
+        Value::Integer(i) => args.push(vec![flag, i.value().to_string()]),
+        Value::Float(f) => args.push(vec![flag, f.value().to_string()]),
+        Value::Boolean(b) => {
+            if *b.value() {
+                args.push(vec![flag]);
+            }
+        }
+        // Repeatable options
+        Value::Array(values) => {
+            for value in values {
+                value_args(key, value, args)?;
+            }
+        }
+        _ => return Err(ConfigError::InvalidValue(key.to_string())),
+    }
+
+    Ok(())
+}
+
+// Tables for options that take name=value, e.g. [symbols] with Return = "⏎" is the same as
+// --symbol Return=⏎
+fn table_args(
+    key: &str,
+    flag: &str,
+    table: &Table,
+    args: &mut Vec<ConfigArg>,
+) -> Result<(), ConfigError> {
+    for (name, item) in table.iter() {
+        let value = item
+            .as_str()
+            .ok_or_else(|| ConfigError::InvalidValue(format!("{key}.{name}")))?;
+        args.push(vec![flag.to_string(), format!("{name}={value}")]);
+    }
+
+    Ok(())
+}
+
+// Keys are the command line options without the leading --, e.g. font-size = 18. Other tables
+// group options by their first word, e.g. size = 18 under [font] is font-size
+fn load_args(path: &Path) -> Result<Vec<ConfigArg>, ConfigError> {
+    let content = fs::read_to_string(path).map_err(ConfigError::Read)?;
+    let document = content.parse::<Document>().map_err(ConfigError::Parse)?;
+
+    let mut args = Vec::new();
+    for (key, item) in document.iter() {
+        match item {
+            Item::Value(value) => value_args(key, value, &mut args)?,
+            Item::Table(table) => match key {
+                "symbols" => table_args(key, "--symbol", table, &mut args)?,
+                // Keys are chords, e.g. "Super+1" = "Workspace 1"
+                "annotations" => table_args(key, "--annotate", table, &mut args)?,
+                "reactions" => table_args(key, "--react", table, &mut args)?,
+                "stats-aliases" => table_args(key, "--stats-alias", table, &mut args)?,
+                _ => {
+                    for (name, item) in table.iter() {
+                        let value = item
+                            .as_value()
+                            .ok_or_else(|| ConfigError::InvalidValue(format!("{key}.{name}")))?;
+                        value_args(&format!("{key}-{name}"), value, &mut args)?;
+                    }
+                }
+            },
+            _ => return Err(ConfigError::InvalidValue(key.to_string())),
+        }
+    }
+
+    Ok(args)
+}
+
+fn is_overridden(flag: &str, cli_flags: &[&str]) -> bool {
+    let exclusive = EXCLUSIVE_OPTIONS
+        .iter()
+        .find(|options| options.contains(&flag))
+        .copied()
+        .unwrap_or(&[]);
+
+    cli_flags.iter().any(|cli_flag| {
+        *cli_flag == flag
+            || exclusive.contains(cli_flag)
+            || cli_flag.strip_prefix("--no-") == flag.strip_prefix("--")
+    })
+}
+
+// The config given with --config, or the default one if it exists
+pub fn path(cli_args: &[String]) -> Option<PathBuf> {
+    let explicit_path = cli_args
+        .iter()
+        .position(|arg| arg == "--config")
+        .and_then(|i| cli_args.get(i + 1))
+        .map(PathBuf::from);
+
+    match explicit_path {
+        Some(v) => Some(v),
+        None => default_path().filter(|v| v.exists()),
+    }
+}
+
+// Inserts the options from the config file before the command line options. Options set on the
+// command line replace the config's, --no-<option> removes one without setting anything
+pub fn merge_args(cli_args: Vec<String>) -> Result<Vec<String>, ConfigError> {
+    let config_args = match path(&cli_args) {
+        Some(v) => load_args(&v)?,
+        None => Vec::new(),
+    };
+
+    // Keep the program name and subcommand in front
+    let split_idx = match cli_args.get(1).map(String::as_str) {
+        Some("kro-test") => 2,
+        _ => 1,
+    }
+    .min(cli_args.len());
+
+    let cli_flags = cli_args[split_idx..]
+        .iter()
+        .map(String::as_str)
+        .filter(|arg| arg.starts_with("--"))
+        .collect::<Vec<_>>();
+
+    let mut ret = cli_args[..split_idx].to_vec();
+    for arg in config_args {
+        if !is_overridden(&arg[0], &cli_flags) {
+            ret.extend(arg);
+        }
+    }
+    ret.extend(
+        cli_args[split_idx..]
+            .iter()
+            .filter(|arg| !arg.starts_with("--no-") || NO_OPTIONS.contains(&arg.as_str()))
+            .cloned(),
+    );
+    Ok(ret)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(s: &[&str]) -> Vec<String> {
+        s.iter().map(|s| s.to_string()).collect()
+    }
+
+    // Writes the config to a file of its own so tests can run in parallel
+    fn merge_with_config(name: &str, config: &str, cli_args: &[&str]) -> Vec<String> {
+        let path = env::temp_dir().join(format!(
+            "keyboard-overlay-config-{}-{name}.toml",
+            std::process::id()
+        ));
+        fs::write(&path, config).unwrap();
+
+        let mut all_args = args(cli_args);
+        all_args.extend(args(&["--config", path.to_str().unwrap()]));
+        let merged = merge_args(all_args).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        // The --config pair stays at the end
+        merged[..merged.len() - 2].to_vec()
+    }
+
+    #[test]
+    fn overridden_by_same_flag() {
+        assert!(is_overridden("--font-size", &["--font-size"]));
+        assert!(!is_overridden("--font-size", &["--font"]));
+        assert!(!is_overridden("--font-size", &[]));
+    }
+
+    #[test]
+    fn overridden_by_exclusive_sibling() {
+        assert!(is_overridden("--replay", &["--event-input-path"]));
+        assert!(is_overridden("--keyboard-devices", &["--backend"]));
+        assert!(is_overridden("--theme", &["--theme-preset"]));
+        assert!(!is_overridden("--theme", &["--theme-text"]));
+    }
+
+    #[test]
+    fn overridden_by_no_form() {
+        assert!(is_overridden("--mouse", &["--no-mouse"]));
+        assert!(!is_overridden("--mouse", &["--no-mouse-trail"]));
+    }
+
+    #[test]
+    fn config_args_go_before_cli_args() {
+        let merged = merge_with_config(
+            "order",
+            "font-size = 18\nmouse = true\n",
+            &["keyboard-overlay", "--anchor", "top-left"],
+        );
+        assert_eq!(
+            merged,
+            args(&[
+                "keyboard-overlay",
+                "--font-size",
+                "18",
+                "--mouse",
+                "--anchor",
+                "top-left"
+            ])
+        );
+    }
+
+    #[test]
+    fn subcommand_stays_in_front() {
+        let merged = merge_with_config(
+            "subcommand",
+            "font-size = 18\n",
+            &["keyboard-overlay", "kro-test"],
+        );
+        assert_eq!(
+            merged,
+            args(&["keyboard-overlay", "kro-test", "--font-size", "18"])
+        );
+    }
+
+    #[test]
+    fn cli_replaces_config() {
+        let merged = merge_with_config(
+            "override",
+            "font-size = 18\nreplay = \"a.evemu\"\n",
+            &["keyboard-overlay", "--font-size", "20", "--backend", "x11"],
+        );
+        assert_eq!(
+            merged,
+            args(&["keyboard-overlay", "--font-size", "20", "--backend", "x11"])
+        );
+    }
+
+    #[test]
+    fn no_form_removes_config_option() {
+        let merged = merge_with_config(
+            "no",
+            "mouse = true\nfont-size = 18\n",
+            &["keyboard-overlay", "--no-mouse", "--no-persist"],
+        );
+        assert_eq!(
+            merged,
+            args(&["keyboard-overlay", "--font-size", "18", "--no-persist"])
+        );
+    }
+
+    #[test]
+    fn tables_become_options() {
+        let merged = merge_with_config(
+            "tables",
+            "[font]\nsize = 18\n\n[symbols]\nReturn = \"⏎\"\n\n[keyboard]\ndevices = [\"a\", \"b\"]\n",
+            &["keyboard-overlay"],
+        );
+        assert_eq!(
+            merged,
+            args(&[
+                "keyboard-overlay",
+                "--font-size",
+                "18",
+                "--symbol",
+                "Return=⏎",
+                "--keyboard-devices",
+                "a",
+                "--keyboard-devices",
+                "b",
+            ])
+        );
+    }
+
+    #[test]
+    fn underscores_become_dashes() {
+        let merged = merge_with_config(
+            "underscore",
+            "reduced_motion = true\nclick_ripple = false\n",
+            &["keyboard-overlay"],
+        );
+        assert_eq!(merged, args(&["keyboard-overlay", "--reduced-motion"]));
+    }
+
+    #[test]
+    fn invalid_config_is_rejected() {
+        let path = env::temp_dir().join(format!(
+            "keyboard-overlay-config-{}-invalid.toml",
+            std::process::id()
+        ));
+        for (config, valid) in [
+            ("font-size = ", false),
+            ("[symbols]\nReturn = 1\n", false),
+            ("when = 1979-05-27\n", false),
+            ("", true),
+        ] {
+            fs::write(&path, config).unwrap();
+            let result = merge_args(args(&[
+                "keyboard-overlay",
+                "--config",
+                path.to_str().unwrap(),
+            ]));
+            assert_eq!(result.is_ok(), valid, "{config:?}");
+        }
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn missing_explicit_config_is_an_error() {
+        let result = merge_args(args(&[
+            "keyboard-overlay",
+            "--config",
+            "/nonexistent/keyboard-overlay.toml",
+        ]));
+        assert!(matches!(result, Err(ConfigError::Read(_))));
+    }
+}
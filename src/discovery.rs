@@ -0,0 +1,92 @@
+use std::{fs, fs::File, path::PathBuf};
+
+use super::{evdev, input_bindings};
+
+pub struct Device {
+    pub path: PathBuf,
+    pub name: String,
+}
+
+// Mice, power buttons and the like also report EV_KEY, only take devices that can type letters
+pub fn is_keyboard(f: &File) -> bool {
+    let types = match evdev::event_bits(f, 0) {
+        Ok(v) => v,
+        Err(_) => return false,
+    };
+
+    if !evdev::test_bit(&types, input_bindings::EV_KEY) {
+        return false;
+    }
+
+    let keys = match evdev::event_bits(f, input_bindings::EV_KEY) {
+        Ok(v) => v,
+        Err(_) => return false,
+    };
+
+    [
+        input_bindings::KEY_Q,
+        input_bindings::KEY_A,
+        input_bindings::KEY_Z,
+        input_bindings::KEY_SPACE,
+        input_bindings::KEY_ENTER,
+    ]
+    .into_iter()
+    .all(|key| evdev::test_bit(&keys, key))
+}
+
+// Anything with a left button and relative axes, touchpads report absolute positions and are
+// left out. Keyboards with a built in pointer are already picked up as keyboards
+pub fn is_mouse(f: &File) -> bool {
+    let has_bit = |ev_type, code| {
+        evdev::event_bits(f, ev_type).is_ok_and(|bits| evdev::test_bit(&bits, code))
+    };
+
+    has_bit(input_bindings::EV_KEY, input_bindings::BTN_LEFT)
+        && has_bit(input_bindings::EV_REL, input_bindings::REL_X)
+        && !is_keyboard(f)
+}
+
+// Scans /dev/input for keyboards, ordered by event node number
+pub fn find_keyboards() -> Vec<Device> {
+    find_devices(is_keyboard)
+}
+
+pub fn find_mice() -> Vec<Device> {
+    find_devices(is_mouse)
+}
+
+fn find_devices(matches: fn(&File) -> bool) -> Vec<Device> {
+    let entries = match fs::read_dir("/dev/input") {
+        Ok(v) => v,
+        Err(_) => return Vec::new(),
+    };
+
+    let mut nodes = entries
+        .filter_map(|entry| {
+            let path = entry.ok()?.path();
+            let node_num = path
+                .file_name()?
+                .to_str()?
+                .strip_prefix("event")?
+                .parse::<u32>()
+                .ok()?;
+            Some((node_num, path))
+        })
+        .collect::<Vec<_>>();
+    nodes.sort();
+
+    nodes
+        .into_iter()
+        .filter_map(|(_, path)| {
+            // Devices we don't have permission to read are skipped, same as ones that don't
+            // match
+            let f = File::open(&path).ok()?;
+            if !matches(&f) {
+                return None;
+            }
+
+            let name = evdev::device_name(&f).unwrap_or_default();
+            Some(Device { path, name })
+        })
+        .collect()
+}
@@ -0,0 +1,162 @@
+use std::{
+    fs::File,
+    io::{Error as IoError, Result as IoResult},
+    mem::{self, MaybeUninit},
+    os::fd::AsRawFd,
+};
+
+use super::input_bindings;
+
+// The EVIOC* request macros from linux/input.h are function-like, so bindgen can't generate them
+const IOC_READ: u64 = 2;
+
+const fn ioc_read(nr: u64, size: usize) -> u64 {
+    (IOC_READ << 30) | ((size as u64) << 16) | ((b'E' as u64) << 8) | nr
+}
+
+const fn eviocgid() -> u64 {
+    ioc_read(0x02, mem::size_of::<input_bindings::input_id>())
+}
+
+const fn eviocgname(len: usize) -> u64 {
+    ioc_read(0x06, len)
+}
+
+const fn eviocgphys(len: usize) -> u64 {
+    ioc_read(0x07, len)
+}
+
+const fn eviocguniq(len: usize) -> u64 {
+    ioc_read(0x08, len)
+}
+
+const fn eviocgprop(len: usize) -> u64 {
+    ioc_read(0x09, len)
+}
+
+const fn eviocgkey(len: usize) -> u64 {
+    ioc_read(0x18, len)
+}
+
+const fn eviocgbit(ev_type: u32, len: usize) -> u64 {
+    ioc_read(0x20 + ev_type as u64, len)
+}
+
+const fn eviocgabs(abs: u32) -> u64 {
+    ioc_read(
+        0x40 + abs as u64,
+        mem::size_of::<input_bindings::input_absinfo>(),
+    )
+}
+
+unsafe fn ioctl<T>(f: &File, request: u64, arg: *mut T) -> IoResult<libc::c_int> {
+    let ret = libc::ioctl(f.as_raw_fd(), request as libc::c_ulong, arg);
+    if ret < 0 {
+        return Err(IoError::last_os_error());
+    }
+
+    Ok(ret)
+}
+
+// Name, phys and uniq are all read the same way
+fn device_string(f: &File, request: fn(usize) -> u64) -> IoResult<String> {
+    let mut buf = vec![0u8; 256];
+    let len = unsafe { ioctl(f, request(buf.len()), buf.as_mut_ptr())? };
+
+    buf.truncate(len as usize);
+    // The returned length includes the nul terminator
+    while buf.last() == Some(&0) {
+        buf.pop();
+    }
+
+    Ok(String::from_utf8_lossy(&buf).to_string())
+}
+
+pub fn device_name(f: &File) -> IoResult<String> {
+    device_string(f, eviocgname)
+}
+
+// Where the device is plugged in, e.g. usb-0000:00:14.0-2/input0
+pub fn device_phys(f: &File) -> IoResult<String> {
+    device_string(f, eviocgphys)
+}
+
+// Serial number, most keyboards don't report one
+pub fn device_uniq(f: &File) -> IoResult<String> {
+    device_string(f, eviocguniq)
+}
+
+pub fn device_id(f: &File) -> IoResult<input_bindings::input_id> {
+    let mut id = MaybeUninit::<input_bindings::input_id>::uninit();
+    unsafe {
+        ioctl(f, eviocgid(), id.as_mut_ptr())?;
+        Ok(id.assume_init())
+    }
+}
+
+pub fn abs_info(f: &File, abs: u32) -> IoResult<input_bindings::input_absinfo> {
+    let mut info = MaybeUninit::<input_bindings::input_absinfo>::uninit();
+    unsafe {
+        ioctl(f, eviocgabs(abs), info.as_mut_ptr())?;
+        Ok(info.assume_init())
+    }
+}
+
+fn bitmask_len(max: u32) -> usize {
+    max as usize / 8 + 1
+}
+
+// Returns the highest code for each event type, for sizing bitmasks
+pub fn event_type_max(ev_type: u32) -> Option<u32> {
+    let ret = match ev_type {
+        0 => input_bindings::EV_MAX,
+        input_bindings::EV_KEY => input_bindings::KEY_MAX,
+        input_bindings::EV_REL => input_bindings::REL_MAX,
+        input_bindings::EV_ABS => input_bindings::ABS_MAX,
+        input_bindings::EV_MSC => input_bindings::MSC_MAX,
+        input_bindings::EV_SW => input_bindings::SW_MAX,
+        input_bindings::EV_LED => input_bindings::LED_MAX,
+        input_bindings::EV_SND => input_bindings::SND_MAX,
+        input_bindings::EV_REP => input_bindings::REP_MAX,
+        input_bindings::EV_FF => input_bindings::FF_MAX,
+        _ => return None,
+    };
+
+    Some(ret)
+}
+
+// Bitmask of supported codes for ev_type, or of supported event types if ev_type is 0
+pub fn event_bits(f: &File, ev_type: u32) -> IoResult<Vec<u8>> {
+    let max = event_type_max(ev_type).unwrap_or(0);
+    let mut buf = vec![0u8; bitmask_len(max)];
+    unsafe {
+        ioctl(f, eviocgbit(ev_type, buf.len()), buf.as_mut_ptr())?;
+    }
+
+    Ok(buf)
+}
+
+pub fn properties(f: &File) -> IoResult<Vec<u8>> {
+    let mut buf = vec![0u8; bitmask_len(input_bindings::INPUT_PROP_MAX)];
+    unsafe {
+        ioctl(f, eviocgprop(buf.len()), buf.as_mut_ptr())?;
+    }
+
+    Ok(buf)
+}
+
+// Bitmask of the keys that are currently down
+pub fn key_state(f: &File) -> IoResult<Vec<u8>> {
+    let mut buf = vec![0u8; bitmask_len(input_bindings::KEY_MAX)];
+    unsafe {
+        ioctl(f, eviocgkey(buf.len()), buf.as_mut_ptr())?;
+    }
+
+    Ok(buf)
+}
+
+pub fn test_bit(bits: &[u8], bit: u32) -> bool {
+    bits.get(bit as usize / 8)
+        .map(|byte| byte & (1 << (bit % 8)) != 0)
+        .unwrap_or(false)
+}
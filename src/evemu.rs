@@ -0,0 +1,340 @@
+use std::{
+    fs::File,
+    io::{BufRead, BufReader, BufWriter, Error as IoError, Write},
+    path::Path,
+    time::Duration,
+};
+
+use super::{evdev, input_bindings};
+
+#[derive(Debug)]
+pub enum EvemuError {
+    Create(IoError),
+    QueryDevice(IoError),
+    Write(IoError),
+    Open(IoError),
+    Read(IoError),
+    InvalidEvent(usize),
+}
+
+// Writes events in the evemu-record format so recordings can be replayed with evemu-play or
+// attached to bug reports for other input projects
+pub struct EvemuRecorder {
+    out: BufWriter<File>,
+    start: Option<Duration>,
+}
+
+impl EvemuRecorder {
+    pub fn create(
+        path: &Path,
+        device: &File,
+        session_code: Option<&str>,
+    ) -> Result<EvemuRecorder, EvemuError> {
+        let out = File::create(path).map_err(EvemuError::Create)?;
+        let mut out = BufWriter::new(out);
+
+        write_header(&mut out, device, session_code)?;
+        out.flush().map_err(EvemuError::Write)?;
+
+        Ok(EvemuRecorder { out, start: None })
+    }
+
+    pub fn record(&mut self, event: &input_bindings::input_event) -> Result<(), EvemuError> {
+        let timestamp = Duration::new(event.time.tv_sec as u64, event.time.tv_usec as u32 * 1000);
+        // Like evemu-record, times are relative to the first event
+        let start = *self.start.get_or_insert(timestamp);
+        let time = timestamp.saturating_sub(start);
+
+        writeln!(
+            self.out,
+            "E: {}.{:06} {:04x} {:04x} {:04}",
+            time.as_secs(),
+            time.subsec_micros(),
+            event.type_,
+            event.code,
+            event.value
+        )
+        .map_err(EvemuError::Write)?;
+
+        // Flush per report so a recording cut short by closing the overlay is still usable
+        if event.type_ as u32 == input_bindings::EV_SYN {
+            self.out.flush().map_err(EvemuError::Write)?;
+        }
+
+        Ok(())
+    }
+}
+
+fn write_header(
+    out: &mut impl Write,
+    device: &File,
+    session_code: Option<&str>,
+) -> Result<(), EvemuError> {
+    let name = evdev::device_name(device).map_err(EvemuError::QueryDevice)?;
+    let id = evdev::device_id(device).map_err(EvemuError::QueryDevice)?;
+    let props = evdev::properties(device).map_err(EvemuError::QueryDevice)?;
+    let types = evdev::event_bits(device, 0).map_err(EvemuError::QueryDevice)?;
+
+    let mut header = format!(
+        "# EVEMU 1.3\n\
+         # Input device name: \"{name}\"\n\
+         # Input device ID: bus {:#x} vendor {:#x} product {:#x} version {:#x}\n",
+        id.bustype, id.vendor, id.product, id.version
+    );
+
+    if let Some(code) = session_code {
+        header.push_str(&format!("# keyboard-overlay session: {code}\n"));
+    }
+
+    header.push_str(&format!("N: {name}\n"));
+    header.push_str(&format!(
+        "I: {:04x} {:04x} {:04x} {:04x}\n",
+        id.bustype, id.vendor, id.product, id.version
+    ));
+
+    header.push_str(&mask_lines("P:", &props));
+
+    for ev_type in 0..=input_bindings::EV_MAX {
+        if evdev::event_type_max(ev_type).is_none() {
+            continue;
+        }
+
+        let bits = if ev_type == 0 {
+            types.clone()
+        } else if evdev::test_bit(&types, ev_type) {
+            evdev::event_bits(device, ev_type).map_err(EvemuError::QueryDevice)?
+        } else {
+            continue;
+        };
+
+        header.push_str(&mask_lines(&format!("B: {ev_type:02x}"), &bits));
+    }
+
+    if evdev::test_bit(&types, input_bindings::EV_ABS) {
+        let abs_bits =
+            evdev::event_bits(device, input_bindings::EV_ABS).map_err(EvemuError::QueryDevice)?;
+        for abs in 0..=input_bindings::ABS_MAX {
+            if !evdev::test_bit(&abs_bits, abs) {
+                continue;
+            }
+
+            let info = evdev::abs_info(device, abs).map_err(EvemuError::QueryDevice)?;
+            header.push_str(&format!(
+                "A: {abs:02x} {} {} {} {} {}\n",
+                info.minimum, info.maximum, info.fuzz, info.flat, info.resolution
+            ));
+        }
+    }
+
+    out.write_all(header.as_bytes()).map_err(EvemuError::Write)
+}
+
+// evemu writes bitmasks 8 bytes per line, zero padded
+fn mask_lines(prefix: &str, bits: &[u8]) -> String {
+    let mut ret = String::new();
+    for chunk in bits.chunks(8) {
+        ret.push_str(prefix);
+        for i in 0..8 {
+            ret.push_str(&format!(" {:02x}", chunk.get(i).copied().unwrap_or(0)));
+        }
+        ret.push('\n');
+    }
+
+    ret
+}
+
+// Reads the events out of an evemu-record or libinput-record file. Timestamps are relative to
+// the start of the recording
+pub fn load_events(path: &Path) -> Result<Vec<input_bindings::input_event>, EvemuError> {
+    let f = File::open(path).map_err(EvemuError::Open)?;
+
+    let mut ret = Vec::new();
+    for (line_idx, line) in BufReader::new(f).lines().enumerate() {
+        let line = line.map_err(EvemuError::Read)?;
+        let line = line.trim();
+
+        let event = if let Some(fields) = line.strip_prefix("E:") {
+            parse_evemu_event(fields)
+        } else if let Some(fields) = line.strip_prefix("- [") {
+            parse_libinput_event(fields)
+        } else {
+            // Device description, comments and anything else we don't replay
+            continue;
+        };
+
+        ret.push(event.ok_or(EvemuError::InvalidEvent(line_idx + 1))?);
+    }
+
+    Ok(ret)
+}
+
+fn make_event(
+    sec: u64,
+    usec: u64,
+    type_: u16,
+    code: u16,
+    value: i32,
+) -> input_bindings::input_event {
+    input_bindings::input_event {
+        time: input_bindings::timeval {
+            tv_sec: sec as _,
+            tv_usec: usec as _,
+        },
+        type_,
+        code,
+        value,
+    }
+}
+
+// e.g. "E: 0.005012 0001 001e 0001"
+fn parse_evemu_event(fields: &str) -> Option<input_bindings::input_event> {
+    let mut fields = fields.split_whitespace();
+    let (sec, usec) = fields.next()?.split_once('.')?;
+    let type_ = u16::from_str_radix(fields.next()?, 16).ok()?;
+    let code = u16::from_str_radix(fields.next()?, 16).ok()?;
+    let value = fields.next()?.parse().ok()?;
+
+    Some(make_event(
+        sec.parse().ok()?,
+        usec.parse().ok()?,
+        type_,
+        code,
+        value,
+    ))
+}
+
+// libinput-record writes evdev events as yaml lists, e.g. "- [  0,   5012,   1,  30,   1] # EV_KEY"
+fn parse_libinput_event(fields: &str) -> Option<input_bindings::input_event> {
+    let (fields, _comment) = fields.split_once(']')?;
+    let fields = fields
+        .split(',')
+        .map(|f| f.trim().parse::<i64>().ok())
+        .collect::<Option<Vec<_>>>()?;
+
+    match fields[..] {
+        [sec, usec, type_, code, value] => Some(make_event(
+            sec as u64,
+            usec as u64,
+            type_ as u16,
+            code as u16,
+            value as i32,
+        )),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{env, fs};
+
+    // (sec, usec, type, code, value)
+    fn fields(event: &input_bindings::input_event) -> (u64, u64, u16, u16, i32) {
+        (
+            event.time.tv_sec as u64,
+            event.time.tv_usec as u64,
+            event.type_,
+            event.code,
+            event.value,
+        )
+    }
+
+    #[test]
+    fn parse_evemu() {
+        let event = parse_evemu_event(" 0.005012 0001 001e 0001").unwrap();
+        assert_eq!(fields(&event), (0, 5012, 1, 30, 1));
+
+        // Relative axes go negative, e.g. scrolling down
+        let event = parse_evemu_event(" 12.000100 0002 0008 -001").unwrap();
+        assert_eq!(fields(&event), (12, 100, 2, 8, -1));
+    }
+
+    #[test]
+    fn parse_evemu_rejects_invalid() {
+        for s in [
+            "",
+            " 0.005012 0001 001e",
+            " 0 0001 001e 0001",
+            " 0.005012 zzzz 001e 0001",
+            " 0.005012 0001 001e one",
+            " x.005012 0001 001e 0001",
+        ] {
+            assert!(parse_evemu_event(s).is_none(), "{s:?}");
+        }
+    }
+
+    #[test]
+    fn parse_libinput() {
+        let event =
+            parse_libinput_event("  0,   5012,   1,  30,   1] # EV_KEY / KEY_A  1").unwrap();
+        assert_eq!(fields(&event), (0, 5012, 1, 30, 1));
+
+        let event = parse_libinput_event("  3,     10,   2,   8,  -1]").unwrap();
+        assert_eq!(fields(&event), (3, 10, 2, 8, -1));
+    }
+
+    #[test]
+    fn parse_libinput_rejects_invalid() {
+        for s in [
+            "",
+            "  0,   5012,   1,  30,   1",
+            "  0,   5012,   1,  30]",
+            "  0,   5012,   1,  30,   1,   2]",
+            "  0,   5012,   1,  KEY_A,   1]",
+        ] {
+            assert!(parse_libinput_event(s).is_none(), "{s:?}");
+        }
+    }
+
+    fn load(name: &str, content: &str) -> Result<Vec<input_bindings::input_event>, EvemuError> {
+        let path = env::temp_dir().join(format!(
+            "keyboard-overlay-evemu-{}-{name}",
+            std::process::id()
+        ));
+        fs::write(&path, content).unwrap();
+        let ret = load_events(&path);
+        fs::remove_file(&path).unwrap();
+        ret
+    }
+
+    #[test]
+    fn load_skips_descriptions() {
+        let events = load(
+            "evemu",
+            "# EVEMU 1.3\nN: Keyboard\nI: 0003 046d c31c 0110\nB: 00 0b 00 00 00 00 00 00 00\n\
+            E: 0.000000 0001 001e 0001\nE: 0.000000 0000 0000 0000\n",
+        )
+        .unwrap();
+        assert_eq!(
+            events.iter().map(fields).collect::<Vec<_>>(),
+            vec![(0, 0, 1, 30, 1), (0, 0, 0, 0, 0)]
+        );
+
+        let events = load(
+            "libinput",
+            "version: 1\ndevices:\n- node: /dev/input/event3\n  events:\n  - evdev:\n    \
+            - [  0,      0,   1,  30,   1] # EV_KEY / KEY_A  1\n    \
+            - [  0,      0,   0,   0,   0] # ------------ SYN_REPORT (0) ----------\n",
+        )
+        .unwrap();
+        assert_eq!(
+            events.iter().map(fields).collect::<Vec<_>>(),
+            vec![(0, 0, 1, 30, 1), (0, 0, 0, 0, 0)]
+        );
+    }
+
+    #[test]
+    fn load_reports_bad_line() {
+        let result = load(
+            "bad",
+            "N: Keyboard\nE: 0.000000 0001 001e 0001\nE: 0.000000 0001\n",
+        );
+        assert!(matches!(result, Err(EvemuError::InvalidEvent(3))));
+    }
+
+    #[test]
+    fn load_missing_file() {
+        let result = load_events(Path::new("/nonexistent/recording.evemu"));
+        assert!(matches!(result, Err(EvemuError::Open(_))));
+    }
+}
@@ -0,0 +1,117 @@
+use std::{
+    sync::mpsc::{self, Receiver, Sender},
+    thread,
+};
+
+use eframe::egui;
+use x11rb::{
+    connection::Connection,
+    errors::{ConnectError, ConnectionError, ReplyError},
+    protocol::{
+        xproto::{
+            Atom, AtomEnum, ChangeWindowAttributesAux, ConnectionExt as _, EventMask, Window,
+        },
+        Event,
+    },
+    rust_connection::RustConnection,
+};
+
+#[derive(Debug)]
+pub enum FocusError {
+    Connect(ConnectError),
+    Connection(ConnectionError),
+    Reply(ReplyError),
+}
+
+struct Atoms {
+    active_window: Atom,
+}
+
+// Reports the class of the focused X11 window, e.g. "firefox", whenever it changes. Relies on the
+// window manager maintaining _NET_ACTIVE_WINDOW
+pub fn spawn_tracker(rx: Receiver<egui::Context>) -> Result<Receiver<String>, FocusError> {
+    let (conn, screen_num) = x11rb::connect(None).map_err(FocusError::Connect)?;
+    let root = conn.setup().roots[screen_num].root;
+
+    let active_window = conn
+        .intern_atom(false, b"_NET_ACTIVE_WINDOW")
+        .map_err(FocusError::Connection)?
+        .reply()
+        .map_err(FocusError::Reply)?
+        .atom;
+    let atoms = Atoms { active_window };
+
+    conn.change_window_attributes(
+        root,
+        &ChangeWindowAttributesAux::new().event_mask(EventMask::PROPERTY_CHANGE),
+    )
+    .map_err(FocusError::Connection)?
+    .check()
+    .map_err(FocusError::Reply)?;
+
+    let (tx, app_rx) = mpsc::channel();
+    thread::spawn(move || tracker_thread(conn, root, atoms, tx, rx));
+    Ok(app_rx)
+}
+
+fn tracker_thread(
+    conn: RustConnection,
+    root: Window,
+    atoms: Atoms,
+    tx: Sender<String>,
+    rx: Receiver<egui::Context>,
+) {
+    let ctx = rx.recv().unwrap();
+    let mut last_app = None;
+
+    loop {
+        let app = focused_app(&conn, root, &atoms).ok().flatten();
+        if let Some(app) = app.filter(|app| last_app.as_ref() != Some(app)) {
+            if tx.send(app.clone()).is_err() {
+                return;
+            }
+            ctx.request_repaint();
+            last_app = Some(app);
+        }
+
+        // Wait for the active window to change
+        loop {
+            match conn.wait_for_event() {
+                Ok(Event::PropertyNotify(e)) if e.atom == atoms.active_window => break,
+                Ok(_) => (),
+                Err(_) => return,
+            }
+        }
+    }
+}
+
+fn focused_app(
+    conn: &RustConnection,
+    root: Window,
+    atoms: &Atoms,
+) -> Result<Option<String>, FocusError> {
+    let active = conn
+        .get_property(false, root, atoms.active_window, AtomEnum::WINDOW, 0, 1)
+        .map_err(FocusError::Connection)?
+        .reply()
+        .map_err(FocusError::Reply)?;
+    let window = match active.value32().and_then(|mut it| it.next()) {
+        Some(0) | None => return Ok(None),
+        Some(v) => v,
+    };
+
+    let class = conn
+        .get_property(false, window, AtomEnum::WM_CLASS, AtomEnum::STRING, 0, 256)
+        .map_err(FocusError::Connection)?
+        .reply()
+        .map_err(FocusError::Reply)?;
+
+    // WM_CLASS is the instance name followed by the class name, both nul terminated
+    let app = class
+        .value
+        .split(|b| *b == 0)
+        .rfind(|s| !s.is_empty())
+        .map(|s| String::from_utf8_lossy(s).into_owned());
+
+    Ok(app)
+}
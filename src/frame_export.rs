@@ -0,0 +1,137 @@
+use std::{
+    collections::VecDeque,
+    fs::{self, File},
+    io::{BufWriter, Error as IoError},
+    path::PathBuf,
+    time::Duration,
+};
+
+use eframe::egui;
+
+use super::input_bindings;
+
+#[derive(Debug)]
+pub enum FrameExportError {
+    CreateDir(IoError),
+    CreateFile(IoError),
+    Encode(png::EncodingError),
+}
+
+// Time of the event into the recording
+pub fn event_offset(event: &input_bindings::input_event) -> Duration {
+    Duration::new(event.time.tv_sec as u64, event.time.tv_usec as u32 * 1000)
+}
+
+// Steps through a recording at a fixed frame rate instead of in real time, saving every rendered
+// frame so video editors can line the overlay up with existing footage
+pub struct FrameExporter {
+    dir: PathBuf,
+    frame_duration: Duration,
+    // --watermark code, stored in every frame's PNG metadata
+    session_code: Option<String>,
+    events: VecDeque<input_bindings::input_event>,
+    frame: u64,
+    waiting_for_screenshot: bool,
+}
+
+impl FrameExporter {
+    pub fn new(
+        dir: PathBuf,
+        frame_rate: f32,
+        events: Vec<input_bindings::input_event>,
+        session_code: Option<String>,
+    ) -> Result<FrameExporter, FrameExportError> {
+        fs::create_dir_all(&dir).map_err(FrameExportError::CreateDir)?;
+
+        Ok(FrameExporter {
+            dir,
+            frame_duration: Duration::from_secs_f32(1.0 / frame_rate),
+            session_code,
+            events: events.into(),
+            frame: 0,
+            waiting_for_screenshot: false,
+        })
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.events.is_empty() && !self.waiting_for_screenshot
+    }
+
+    // Saves the screenshot of the last frame if it has arrived. Returns true once we're ready to
+    // render the next frame
+    pub fn poll_screenshot(&mut self, ctx: &egui::Context) -> Result<bool, FrameExportError> {
+        if !self.waiting_for_screenshot {
+            return Ok(true);
+        }
+
+        let image = ctx.input(|i| {
+            i.raw.events.iter().find_map(|e| match e {
+                egui::Event::Screenshot { image, .. } => Some(image.clone()),
+                _ => None,
+            })
+        });
+
+        let image = match image {
+            Some(v) => v,
+            None => return Ok(false),
+        };
+
+        self.save_frame(&image)?;
+        self.frame += 1;
+        self.waiting_for_screenshot = false;
+        Ok(true)
+    }
+
+    // Time into the recording the next frame shows
+    pub fn frame_end(&self) -> Duration {
+        self.frame_duration * (self.frame as u32 + 1)
+    }
+
+    // Events that happen before the end of the next frame
+    pub fn next_frame_events(&mut self) -> Vec<input_bindings::input_event> {
+        let frame_end = self.frame_end();
+
+        let mut ret = Vec::new();
+        while let Some(event) = self.events.front() {
+            if event_offset(event) >= frame_end {
+                break;
+            }
+            ret.extend(self.events.pop_front());
+        }
+
+        ret
+    }
+
+    pub fn request_screenshot(&mut self, ctx: &egui::Context) {
+        self.waiting_for_screenshot = true;
+        ctx.send_viewport_cmd(egui::ViewportCommand::Screenshot);
+        ctx.request_repaint();
+    }
+
+    fn save_frame(&self, image: &egui::ColorImage) -> Result<(), FrameExportError> {
+        let path = self.dir.join(format!("frame_{:06}.png", self.frame));
+        let f = File::create(path).map_err(FrameExportError::CreateFile)?;
+
+        let [width, height] = image.size;
+        let mut encoder = png::Encoder::new(BufWriter::new(f), width as u32, height as u32);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        if let Some(code) = &self.session_code {
+            encoder
+                .add_text_chunk("Session code".to_string(), code.clone())
+                .map_err(FrameExportError::Encode)?;
+        }
+
+        // Keep the alpha channel so the frames can be composited directly
+        let data = image
+            .pixels
+            .iter()
+            .flat_map(|p| p.to_srgba_unmultiplied())
+            .collect::<Vec<_>>();
+
+        encoder
+            .write_header()
+            .and_then(|mut writer| writer.write_image_data(&data))
+            .map_err(FrameExportError::Encode)
+    }
+}
@@ -0,0 +1,96 @@
+use std::{
+    collections::VecDeque,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use eframe::egui::{self, Align2, Color32, FontFamily, RichText};
+
+// Event rate is averaged over this long
+const RATE_WINDOW: Duration = Duration::from_secs(5);
+
+// Per device statistics, updated by its reader thread
+pub struct DeviceHealth {
+    name: String,
+    path: PathBuf,
+    recent_events: VecDeque<Instant>,
+    last_event: Option<Instant>,
+    errors: u64,
+}
+
+pub type SharedHealth = Arc<Mutex<DeviceHealth>>;
+
+impl DeviceHealth {
+    pub fn new_shared(name: String, path: PathBuf) -> SharedHealth {
+        Arc::new(Mutex::new(DeviceHealth {
+            name,
+            path,
+            recent_events: VecDeque::new(),
+            last_event: None,
+            errors: 0,
+        }))
+    }
+
+    pub fn record_event(&mut self) {
+        let now = Instant::now();
+        self.last_event = Some(now);
+        self.recent_events.push_back(now);
+        self.expire_events();
+    }
+
+    pub fn record_error(&mut self) {
+        self.errors += 1;
+    }
+
+    fn expire_events(&mut self) {
+        while let Some(t) = self.recent_events.front() {
+            if t.elapsed() < RATE_WINDOW {
+                break;
+            }
+            self.recent_events.pop_front();
+        }
+    }
+
+    fn summary(&mut self) -> String {
+        self.expire_events();
+        let rate = self.recent_events.len() as f32 / RATE_WINDOW.as_secs_f32();
+        let last_event = match self.last_event {
+            Some(t) => format!("{:.1}s ago", t.elapsed().as_secs_f32()),
+            None => "never".to_string(),
+        };
+
+        format!(
+            "{} ({})\n  {rate:.1} events/s, last {last_event}, {} errors",
+            self.name,
+            self.path.display(),
+            self.errors
+        )
+    }
+}
+
+pub fn render(ctx: &egui::Context, devices: &[SharedHealth]) {
+    egui::Area::new("health")
+        .anchor(Align2::LEFT_TOP, [8.0, 8.0])
+        .show(ctx, |ui| {
+            egui::Frame::none()
+                .fill(Color32::from_black_alpha(200))
+                .rounding(4.0)
+                .inner_margin(6.0)
+                .show(ui, |ui| {
+                    if devices.is_empty() {
+                        ui.label(RichText::new("No input devices").color(Color32::GRAY));
+                    }
+
+                    for device in devices {
+                        let text = device.lock().unwrap().summary();
+                        let label_text = RichText::new(text)
+                            .family(FontFamily::Monospace)
+                            .color(Color32::WHITE)
+                            .size(13.0);
+
+                        ui.label(label_text);
+                    }
+                });
+        });
+}
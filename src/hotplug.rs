@@ -0,0 +1,126 @@
+use std::{
+    ffi::OsStr,
+    fs::{self, File},
+    io::Result as IoResult,
+    os::unix::ffi::OsStrExt,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use super::{evdev, inotify::Inotify};
+
+// Event node numbers change when a device is replugged, so recognize it by what it reports.
+// Identical keyboards only differ in the port they're plugged into, or their serial if they have
+// one, so a device is only reconnected when it comes back on the same port
+#[derive(Eq, PartialEq)]
+pub struct DeviceIdentity {
+    name: String,
+    id: (u16, u16, u16, u16),
+    // Empty if the device doesn't report them
+    phys: String,
+    uniq: String,
+}
+
+// Devices that have a reader, including ones waiting to be reconnected. Discovery leaves those to
+// their reader
+pub type ClaimedDevices = Arc<Mutex<Vec<DeviceIdentity>>>;
+
+impl DeviceIdentity {
+    pub fn of(f: &File) -> Option<DeviceIdentity> {
+        let name = evdev::device_name(f).ok()?;
+        let id = evdev::device_id(f).ok()?;
+
+        Some(DeviceIdentity {
+            name,
+            id: (id.bustype, id.vendor, id.product, id.version),
+            phys: evdev::device_phys(f).unwrap_or_default(),
+            uniq: evdev::device_uniq(f).unwrap_or_default(),
+        })
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+fn find_device(identity: &DeviceIdentity) -> Option<File> {
+    fs::read_dir("/dev/input")
+        .ok()?
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_name().as_bytes().starts_with(b"event"))
+        .filter_map(|entry| File::open(entry.path()).ok())
+        .find(|f| DeviceIdentity::of(f).as_ref() == Some(identity))
+}
+
+// Blocks until the device shows up in /dev/input again, or until timeout has passed
+pub fn wait_for_device(
+    identity: &DeviceIdentity,
+    timeout: Option<Duration>,
+) -> IoResult<Option<File>> {
+    let deadline = timeout.map(|t| Instant::now() + t);
+    let mut inotify = Inotify::new()?;
+    // Nodes are created as root and get their permissions from udev afterwards, so we also need
+    // to retry on attribute changes
+    inotify.add_watch(Path::new("/dev/input"), libc::IN_CREATE | libc::IN_ATTRIB)?;
+
+    loop {
+        if let Some(f) = find_device(identity) {
+            return Ok(Some(f));
+        }
+
+        if let Some(deadline) = deadline {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() || !inotify.wait(remaining)? {
+                return Ok(None);
+            }
+        }
+
+        inotify.read_names()?;
+    }
+}
+
+// Calls on_new for every device that shows up in /dev/input, passes matches and isn't claimed
+// yet. The device is claimed first. Only returns on error
+pub fn watch_new_devices(
+    claimed: &ClaimedDevices,
+    matches: fn(&File) -> bool,
+    mut on_new: impl FnMut(PathBuf, File),
+) -> IoResult<()> {
+    let mut inotify = Inotify::new()?;
+    // Same as in wait_for_device, the node may not be readable until its attributes change
+    inotify.add_watch(Path::new("/dev/input"), libc::IN_CREATE | libc::IN_ATTRIB)?;
+
+    loop {
+        for name in inotify.read_names()? {
+            if !name.starts_with(b"event") {
+                continue;
+            }
+
+            let path = Path::new("/dev/input").join(OsStr::from_bytes(&name));
+            let f = match File::open(&path) {
+                Ok(v) => v,
+                Err(_) => continue,
+            };
+
+            if !matches(&f) {
+                continue;
+            }
+
+            let identity = match DeviceIdentity::of(&f) {
+                Some(v) => v,
+                None => continue,
+            };
+
+            {
+                let mut claimed = claimed.lock().unwrap();
+                if claimed.contains(&identity) {
+                    continue;
+                }
+                claimed.push(identity);
+            }
+
+            on_new(path, f);
+        }
+    }
+}
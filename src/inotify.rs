@@ -0,0 +1,80 @@
+use std::{
+    ffi::CString,
+    fs::File,
+    io::{Error as IoError, ErrorKind, Read, Result as IoResult},
+    mem,
+    os::{
+        fd::{AsRawFd, FromRawFd},
+        unix::ffi::OsStrExt,
+    },
+    path::Path,
+    time::Duration,
+};
+
+pub struct Inotify {
+    f: File,
+}
+
+impl Inotify {
+    pub fn new() -> IoResult<Inotify> {
+        let fd = unsafe { libc::inotify_init1(libc::IN_CLOEXEC) };
+        if fd < 0 {
+            return Err(IoError::last_os_error());
+        }
+
+        Ok(Inotify {
+            f: unsafe { File::from_raw_fd(fd) },
+        })
+    }
+
+    pub fn add_watch(&self, path: &Path, mask: u32) -> IoResult<()> {
+        let path_c = CString::new(path.as_os_str().as_bytes())
+            .map_err(|e| IoError::new(ErrorKind::InvalidInput, e))?;
+        let ret = unsafe { libc::inotify_add_watch(self.f.as_raw_fd(), path_c.as_ptr(), mask) };
+        if ret < 0 {
+            return Err(IoError::last_os_error());
+        }
+
+        Ok(())
+    }
+
+    // Returns whether something happened within timeout, without reading it
+    pub fn wait(&self, timeout: Duration) -> IoResult<bool> {
+        let mut fd = libc::pollfd {
+            fd: self.f.as_raw_fd(),
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        let timeout_ms = timeout.as_millis().min(libc::c_int::MAX as u128) as libc::c_int;
+        let ret = unsafe { libc::poll(&mut fd, 1, timeout_ms) };
+        if ret < 0 {
+            return Err(IoError::last_os_error());
+        }
+
+        Ok(ret > 0)
+    }
+
+    // Blocks until something happens, returns the names of the files in watched directories that
+    // changed
+    pub fn read_names(&mut self) -> IoResult<Vec<Vec<u8>>> {
+        let mut buf = vec![0u8; 4096];
+        let len = self.f.read(&mut buf)?;
+
+        let mut ret = Vec::new();
+        let mut offset = 0;
+        let header_len = mem::size_of::<libc::inotify_event>();
+        while offset + header_len <= len {
+            let event = unsafe {
+                (buf.as_ptr().add(offset) as *const libc::inotify_event).read_unaligned()
+            };
+            let name = &buf[offset + header_len..offset + header_len + event.len as usize];
+            // The name is nul padded
+            let name = name.split(|b| *b == 0).next().unwrap_or_default();
+            ret.push(name.to_vec());
+
+            offset += header_len + event.len as usize;
+        }
+
+        Ok(ret)
+    }
+}
@@ -0,0 +1,264 @@
+use std::{
+    fs,
+    io::{BufRead, BufReader, Error as IoError, Write},
+    mem,
+    os::{
+        fd::AsRawFd,
+        unix::{
+            fs::{FileTypeExt, PermissionsExt},
+            net::{UnixListener, UnixStream},
+        },
+    },
+    path::Path,
+    sync::mpsc::{Receiver, Sender},
+    thread,
+};
+
+use eframe::egui;
+
+use super::{chord::Chord, xkbcommon::KeymapSource};
+
+#[derive(Debug)]
+pub enum IpcError {
+    Bind(IoError),
+    SetPermissions(IoError),
+}
+
+#[derive(Debug)]
+pub enum IpcCommand {
+    StartCapture,
+    StopCapture,
+    Show,
+    Hide,
+    SetKeymap(KeymapSource),
+    ReplaySpeed(f32),
+    // Fast forward to the next use of the chord
+    ReplaySeek(Chord),
+    // Start a new segment, numbered if there's no name
+    Segment(Option<String>),
+}
+
+impl IpcCommand {
+    fn parse(line: &str) -> Option<IpcCommand> {
+        let (command, arg) = line.trim().split_once(' ').unwrap_or((line.trim(), ""));
+        let ret = match (command, arg) {
+            ("start-capture", "") => IpcCommand::StartCapture,
+            ("stop-capture", "") => IpcCommand::StopCapture,
+            ("show", "") => IpcCommand::Show,
+            ("hide", "") => IpcCommand::Hide,
+            ("set-keymap", arg) => IpcCommand::SetKeymap(KeymapSource::parse(arg)?),
+            ("replay-speed", arg) => {
+                IpcCommand::ReplaySpeed(arg.trim().parse().ok().filter(|v: &f32| *v > 0.0)?)
+            }
+            ("replay-seek", arg) => IpcCommand::ReplaySeek(Chord::parse(arg.trim())?),
+            ("segment", "") => IpcCommand::Segment(None),
+            ("segment", arg) => IpcCommand::Segment(Some(arg.trim().to_string())),
+            _ => return None,
+        };
+
+        Some(ret)
+    }
+}
+
+pub fn spawn_listener(
+    path: &Path,
+    tx: Sender<IpcCommand>,
+    rx: Receiver<egui::Context>,
+) -> Result<(), IpcError> {
+    remove_stale_socket(path);
+    let listener = UnixListener::bind(path).map_err(IpcError::Bind)?;
+
+    // Other users must not be able to read the event stream or change what we capture
+    fs::set_permissions(path, fs::Permissions::from_mode(0o600))
+        .map_err(IpcError::SetPermissions)?;
+
+    thread::spawn(move || listener_thread(listener, tx, rx));
+    Ok(())
+}
+
+// Only ever remove a socket, a typo in the path should not delete someone's file
+pub fn remove_stale_socket(path: &Path) {
+    if let Ok(metadata) = fs::symlink_metadata(path) {
+        if metadata.file_type().is_socket() {
+            let _ = fs::remove_file(path);
+        }
+    }
+}
+
+fn listener_thread(listener: UnixListener, tx: Sender<IpcCommand>, rx: Receiver<egui::Context>) {
+    let ctx = rx.recv().unwrap();
+
+    for stream in listener.incoming() {
+        let stream = match stream {
+            Ok(v) => v,
+            Err(_) => continue,
+        };
+
+        let tx = tx.clone();
+        let ctx = ctx.clone();
+        thread::spawn(move || handle_client(stream, tx, ctx));
+    }
+}
+
+fn peer_uid(stream: &UnixStream) -> Option<u32> {
+    let mut cred = libc::ucred {
+        pid: 0,
+        uid: 0,
+        gid: 0,
+    };
+    let mut len = mem::size_of::<libc::ucred>() as libc::socklen_t;
+
+    let ret = unsafe {
+        libc::getsockopt(
+            stream.as_raw_fd(),
+            libc::SOL_SOCKET,
+            libc::SO_PEERCRED,
+            &mut cred as *mut libc::ucred as *mut libc::c_void,
+            &mut len,
+        )
+    };
+
+    if ret != 0 {
+        return None;
+    }
+
+    Some(cred.uid)
+}
+
+fn handle_client(stream: UnixStream, tx: Sender<IpcCommand>, ctx: egui::Context) {
+    let mut writer = match stream.try_clone() {
+        Ok(v) => v,
+        Err(_) => return,
+    };
+
+    // Permissions are only tightened after bind, so there is a window where anyone could have
+    // connected
+    if peer_uid(&writer) != Some(unsafe { libc::getuid() }) {
+        let _ = writeln!(writer, "error: permission denied");
+        return;
+    }
+
+    for line in BufReader::new(stream).lines() {
+        let line = match line {
+            Ok(v) => v,
+            Err(_) => return,
+        };
+
+        let response = match IpcCommand::parse(&line) {
+            Some(command) => {
+                if tx.send(command).is_err() {
+                    return;
+                }
+                ctx.request_repaint();
+                "ok".to_string()
+            }
+            None => format!("error: unknown command {}", line.trim()),
+        };
+
+        if writeln!(writer, "{response}").is_err() {
+            return;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Modifiers;
+
+    #[test]
+    fn parse_commands_without_args() {
+        assert!(matches!(
+            IpcCommand::parse("start-capture"),
+            Some(IpcCommand::StartCapture)
+        ));
+        assert!(matches!(
+            IpcCommand::parse("stop-capture\n"),
+            Some(IpcCommand::StopCapture)
+        ));
+        assert!(matches!(
+            IpcCommand::parse(" show "),
+            Some(IpcCommand::Show)
+        ));
+        assert!(matches!(IpcCommand::parse("hide"), Some(IpcCommand::Hide)));
+        assert!(matches!(
+            IpcCommand::parse("segment"),
+            Some(IpcCommand::Segment(None))
+        ));
+    }
+
+    #[test]
+    fn parse_set_keymap() {
+        match IpcCommand::parse("set-keymap layout=de variant=nodeadkeys") {
+            Some(IpcCommand::SetKeymap(KeymapSource::Names(names))) => {
+                assert_eq!(names.layout.as_deref(), Some("de"));
+                assert_eq!(names.variant.as_deref(), Some("nodeadkeys"));
+                assert_eq!(names.rules, None);
+            }
+            v => panic!("{v:?}"),
+        }
+
+        match IpcCommand::parse("set-keymap /tmp/out.xkb") {
+            Some(IpcCommand::SetKeymap(KeymapSource::File(path))) => {
+                assert_eq!(path, Path::new("/tmp/out.xkb"));
+            }
+            v => panic!("{v:?}"),
+        }
+    }
+
+    #[test]
+    fn parse_replay_speed() {
+        assert!(matches!(
+            IpcCommand::parse("replay-speed 2.5"),
+            Some(IpcCommand::ReplaySpeed(v)) if v == 2.5
+        ));
+        for s in [
+            "replay-speed",
+            "replay-speed 0",
+            "replay-speed -1",
+            "replay-speed NaN",
+            "replay-speed fast",
+        ] {
+            assert!(IpcCommand::parse(s).is_none(), "{s:?}");
+        }
+    }
+
+    #[test]
+    fn parse_replay_seek() {
+        match IpcCommand::parse("replay-seek Ctrl+s") {
+            Some(IpcCommand::ReplaySeek(chord)) => {
+                let ctrl = Modifiers {
+                    ctrl: true,
+                    ..Default::default()
+                };
+                assert!(chord.matches_key("s", &ctrl));
+            }
+            v => panic!("{v:?}"),
+        }
+        assert!(IpcCommand::parse("replay-seek").is_none());
+        assert!(IpcCommand::parse("replay-seek Hyper+s").is_none());
+    }
+
+    #[test]
+    fn parse_segment_name() {
+        match IpcCommand::parse("segment  Part two ") {
+            Some(IpcCommand::Segment(Some(name))) => assert_eq!(name, "Part two"),
+            v => panic!("{v:?}"),
+        }
+    }
+
+    #[test]
+    fn parse_rejects_unknown() {
+        for s in [
+            "",
+            "\n",
+            "start",
+            "show now",
+            "hide 1",
+            "Show",
+            "start-capture x",
+        ] {
+            assert!(IpcCommand::parse(s).is_none(), "{s:?}");
+        }
+    }
+}
@@ -0,0 +1,32 @@
+use super::{KeyHistoryItem, Modifiers};
+
+// Which history items get passed on to outputs other than the overlay
+#[derive(Clone, Copy, Eq, PartialEq)]
+pub enum KeyFilter {
+    All,
+    // Only keys pressed with a modifier
+    Chords,
+    // Only undo/redo/clipboard chords
+    Actions,
+}
+
+impl KeyFilter {
+    pub fn parse(s: &str) -> Option<KeyFilter> {
+        let ret = match s {
+            "all" => KeyFilter::All,
+            "chords" => KeyFilter::Chords,
+            "actions" => KeyFilter::Actions,
+            _ => return None,
+        };
+
+        Some(ret)
+    }
+
+    pub fn accepts(&self, item: &KeyHistoryItem) -> bool {
+        match self {
+            KeyFilter::All => true,
+            KeyFilter::Chords => item.modifiers != Modifiers::default(),
+            KeyFilter::Actions => item.action.is_some(),
+        }
+    }
+}
@@ -0,0 +1,390 @@
+use std::{collections::BTreeMap, time::Duration};
+
+use eframe::egui::{self, Align2, Color32, FontFamily, FontId, Rect, RichText, Sense, Vec2};
+
+use super::{input_bindings, theme::Theme, HeldKey};
+
+const CELL_SIZE: f32 = 28.0;
+const CELL_SPACING: f32 = 4.0;
+// How long a key has to be held for its hold bar to fill up
+const HOLD_BAR_FULL: Duration = Duration::from_secs(1);
+
+struct GridKey {
+    code: u16,
+    label: String,
+}
+
+fn cell(code: u32, label: &str) -> Option<GridKey> {
+    Some(GridKey {
+        code: code as u16,
+        label: label.to_string(),
+    })
+}
+
+// Physical key names usable in grid definitions
+const KEY_NAMES: &[(&str, u32)] = &[
+    ("esc", input_bindings::KEY_ESC),
+    ("1", input_bindings::KEY_1),
+    ("2", input_bindings::KEY_2),
+    ("3", input_bindings::KEY_3),
+    ("4", input_bindings::KEY_4),
+    ("5", input_bindings::KEY_5),
+    ("6", input_bindings::KEY_6),
+    ("7", input_bindings::KEY_7),
+    ("8", input_bindings::KEY_8),
+    ("9", input_bindings::KEY_9),
+    ("0", input_bindings::KEY_0),
+    ("minus", input_bindings::KEY_MINUS),
+    ("equal", input_bindings::KEY_EQUAL),
+    ("backspace", input_bindings::KEY_BACKSPACE),
+    ("tab", input_bindings::KEY_TAB),
+    ("q", input_bindings::KEY_Q),
+    ("w", input_bindings::KEY_W),
+    ("e", input_bindings::KEY_E),
+    ("r", input_bindings::KEY_R),
+    ("t", input_bindings::KEY_T),
+    ("y", input_bindings::KEY_Y),
+    ("u", input_bindings::KEY_U),
+    ("i", input_bindings::KEY_I),
+    ("o", input_bindings::KEY_O),
+    ("p", input_bindings::KEY_P),
+    ("leftbrace", input_bindings::KEY_LEFTBRACE),
+    ("rightbrace", input_bindings::KEY_RIGHTBRACE),
+    ("enter", input_bindings::KEY_ENTER),
+    ("lctrl", input_bindings::KEY_LEFTCTRL),
+    ("a", input_bindings::KEY_A),
+    ("s", input_bindings::KEY_S),
+    ("d", input_bindings::KEY_D),
+    ("f", input_bindings::KEY_F),
+    ("g", input_bindings::KEY_G),
+    ("h", input_bindings::KEY_H),
+    ("j", input_bindings::KEY_J),
+    ("k", input_bindings::KEY_K),
+    ("l", input_bindings::KEY_L),
+    ("semicolon", input_bindings::KEY_SEMICOLON),
+    ("apostrophe", input_bindings::KEY_APOSTROPHE),
+    ("grave", input_bindings::KEY_GRAVE),
+    ("lshift", input_bindings::KEY_LEFTSHIFT),
+    ("backslash", input_bindings::KEY_BACKSLASH),
+    ("z", input_bindings::KEY_Z),
+    ("x", input_bindings::KEY_X),
+    ("c", input_bindings::KEY_C),
+    ("v", input_bindings::KEY_V),
+    ("b", input_bindings::KEY_B),
+    ("n", input_bindings::KEY_N),
+    ("m", input_bindings::KEY_M),
+    ("comma", input_bindings::KEY_COMMA),
+    ("dot", input_bindings::KEY_DOT),
+    ("slash", input_bindings::KEY_SLASH),
+    ("rshift", input_bindings::KEY_RIGHTSHIFT),
+    ("lalt", input_bindings::KEY_LEFTALT),
+    ("space", input_bindings::KEY_SPACE),
+    ("capslock", input_bindings::KEY_CAPSLOCK),
+    ("f1", input_bindings::KEY_F1),
+    ("f2", input_bindings::KEY_F2),
+    ("f3", input_bindings::KEY_F3),
+    ("f4", input_bindings::KEY_F4),
+    ("f5", input_bindings::KEY_F5),
+    ("f6", input_bindings::KEY_F6),
+    ("f7", input_bindings::KEY_F7),
+    ("f8", input_bindings::KEY_F8),
+    ("f9", input_bindings::KEY_F9),
+    ("f10", input_bindings::KEY_F10),
+    ("f11", input_bindings::KEY_F11),
+    ("f12", input_bindings::KEY_F12),
+    ("rctrl", input_bindings::KEY_RIGHTCTRL),
+    ("ralt", input_bindings::KEY_RIGHTALT),
+    ("home", input_bindings::KEY_HOME),
+    ("up", input_bindings::KEY_UP),
+    ("pageup", input_bindings::KEY_PAGEUP),
+    ("left", input_bindings::KEY_LEFT),
+    ("right", input_bindings::KEY_RIGHT),
+    ("end", input_bindings::KEY_END),
+    ("down", input_bindings::KEY_DOWN),
+    ("pagedown", input_bindings::KEY_PAGEDOWN),
+    ("insert", input_bindings::KEY_INSERT),
+    ("delete", input_bindings::KEY_DELETE),
+    ("lsuper", input_bindings::KEY_LEFTMETA),
+    ("rsuper", input_bindings::KEY_RIGHTMETA),
+];
+
+const KEYBOARD_DEFINITION: &str = "\
+    esc _ f1 f2 f3 f4 f5 f6 f7 f8 f9 f10 f11 f12 /\
+    grave 1 2 3 4 5 6 7 8 9 0 minus equal backspace /\
+    tab q w e r t y u i o p leftbrace rightbrace backslash /\
+    capslock a s d f g h j k l semicolon apostrophe enter /\
+    lshift z x c v b n m comma dot slash rshift _ up /\
+    lctrl lsuper lalt space ralt rsuper rctrl _ _ _ _ _ left down right";
+
+// Key names are too long for a cell in the full keyboard
+const SHORT_LABELS: &[(&str, &str)] = &[
+    ("esc", "Esc"),
+    ("grave", "`"),
+    ("minus", "-"),
+    ("equal", "="),
+    ("backspace", "⌫"),
+    ("tab", "⇥"),
+    ("leftbrace", "["),
+    ("rightbrace", "]"),
+    ("backslash", "\\"),
+    ("capslock", "⇪"),
+    ("semicolon", ";"),
+    ("apostrophe", "'"),
+    ("enter", "⏎"),
+    ("lshift", "⇧"),
+    ("rshift", "⇧"),
+    ("comma", ","),
+    ("dot", "."),
+    ("slash", "/"),
+    ("lctrl", "Ctl"),
+    ("rctrl", "Ctl"),
+    ("lsuper", "◆"),
+    ("rsuper", "◆"),
+    ("lalt", "Alt"),
+    ("ralt", "Alt"),
+    ("space", "␣"),
+    ("up", "↑"),
+    ("left", "←"),
+    ("down", "↓"),
+    ("right", "→"),
+    ("f1", "F1"),
+    ("f2", "F2"),
+    ("f3", "F3"),
+    ("f4", "F4"),
+    ("f5", "F5"),
+    ("f6", "F6"),
+    ("f7", "F7"),
+    ("f8", "F8"),
+    ("f9", "F9"),
+    ("f10", "F10"),
+    ("f11", "F11"),
+    ("f12", "F12"),
+];
+
+fn key_code_from_name(name: &str) -> Option<u32> {
+    KEY_NAMES
+        .iter()
+        .find(|(key_name, _)| key_name.eq_ignore_ascii_case(name))
+        .map(|(_, code)| *code)
+}
+
+// Press counts for coloring keys by how often they're used
+struct Heatmap {
+    counts: BTreeMap<u16, u64>,
+    cold: Color32,
+    hot: Color32,
+}
+
+impl Heatmap {
+    fn color(&self, code: u16) -> Option<Color32> {
+        let count = *self.counts.get(&code)?;
+        let max = self.counts.values().copied().max()?;
+        let t = count as f32 / max as f32;
+
+        let mix = |a: u8, b: u8| (a as f32 + (b as f32 - a as f32) * t).round() as u8;
+        Some(Color32::from_rgb(
+            mix(self.cold.r(), self.hot.r()),
+            mix(self.cold.g(), self.hot.g()),
+            mix(self.cold.b(), self.hot.b()),
+        ))
+    }
+}
+
+// Fixed layout of keys that light up while held
+pub struct KeyGrid {
+    rows: Vec<Vec<Option<GridKey>>>,
+    // Set when the grid should show how long the last key was held for
+    frame_rate: Option<f32>,
+    last_release: Option<(String, Duration)>,
+    hold_bars: bool,
+    heatmap: Option<Heatmap>,
+}
+
+impl KeyGrid {
+    fn new(rows: Vec<Vec<Option<GridKey>>>) -> KeyGrid {
+        KeyGrid {
+            rows,
+            frame_rate: None,
+            last_release: None,
+            hold_bars: false,
+            heatmap: None,
+        }
+    }
+
+    // Rows are separated by '/', keys by whitespace and '_' leaves a gap, e.g. "_ w _ / a s d"
+    pub fn parse(definition: &str) -> Option<KeyGrid> {
+        let mut rows = Vec::new();
+
+        for row_s in definition.split('/') {
+            let mut row = Vec::new();
+            for name in row_s.split_whitespace() {
+                if name == "_" {
+                    row.push(None);
+                    continue;
+                }
+
+                let label = if name.chars().count() == 1 {
+                    name.to_uppercase()
+                } else {
+                    name.to_string()
+                };
+                row.push(cell(key_code_from_name(name)?, &label));
+            }
+            rows.push(row);
+        }
+
+        Some(KeyGrid::new(rows))
+    }
+
+    // US ANSI keyboard with the arrow keys tucked under the right shift, no insert/home/page cluster
+    // or numpad
+    pub fn keyboard() -> KeyGrid {
+        let mut grid = KeyGrid::parse(KEYBOARD_DEFINITION).expect("Invalid keyboard definition");
+        for key in grid.rows.iter_mut().flatten().flatten() {
+            if let Some((_, label)) = SHORT_LABELS.iter().find(|(name, _)| key.label == *name) {
+                key.label = label.to_string();
+            }
+        }
+
+        grid
+    }
+
+    pub fn with_timing(mut self, frame_rate: f32) -> KeyGrid {
+        self.frame_rate = Some(frame_rate);
+        self
+    }
+
+    pub fn with_hold_bars(mut self) -> KeyGrid {
+        self.hold_bars = true;
+        self
+    }
+
+    pub fn with_heatmap(mut self, cold: Color32, hot: Color32) -> KeyGrid {
+        self.heatmap = Some(Heatmap {
+            counts: BTreeMap::new(),
+            cold,
+            hot,
+        });
+        self
+    }
+
+    pub fn record_press(&mut self, code: u16) {
+        if let Some(heatmap) = &mut self.heatmap {
+            *heatmap.counts.entry(code).or_default() += 1;
+        }
+    }
+
+    pub fn reset_heatmap(&mut self) {
+        if let Some(heatmap) = &mut self.heatmap {
+            heatmap.counts.clear();
+        }
+    }
+
+    pub fn record_release(&mut self, code: u16, held_for: Duration) {
+        if self.frame_rate.is_none() {
+            return;
+        }
+
+        let key = self
+            .rows
+            .iter()
+            .flatten()
+            .flatten()
+            .find(|k| k.code == code);
+        if let Some(key) = key {
+            self.last_release = Some((key.label.clone(), held_for));
+        }
+    }
+
+    pub fn dpad_arrows() -> KeyGrid {
+        KeyGrid::new(vec![
+            vec![None, cell(input_bindings::KEY_UP, "↑"), None],
+            vec![
+                cell(input_bindings::KEY_LEFT, "←"),
+                cell(input_bindings::KEY_DOWN, "↓"),
+                cell(input_bindings::KEY_RIGHT, "→"),
+            ],
+        ])
+    }
+
+    // Uses physical key positions, so this works regardless of layout
+    pub fn dpad_wasd() -> KeyGrid {
+        KeyGrid::new(vec![
+            vec![None, cell(input_bindings::KEY_W, "W"), None],
+            vec![
+                cell(input_bindings::KEY_A, "A"),
+                cell(input_bindings::KEY_S, "S"),
+                cell(input_bindings::KEY_D, "D"),
+            ],
+        ])
+    }
+
+    pub fn render(
+        &self,
+        ui: &mut egui::Ui,
+        held_keys: &BTreeMap<u16, HeldKey>,
+        now: Duration,
+        theme: &Theme,
+    ) {
+        let num_cols = self.rows.iter().map(Vec::len).max().unwrap_or(0);
+        let stride = CELL_SIZE + CELL_SPACING;
+        let size = Vec2::new(
+            num_cols as f32 * stride - CELL_SPACING,
+            self.rows.len() as f32 * stride - CELL_SPACING,
+        );
+
+        let (rect, _) = ui.allocate_exact_size(size.max(Vec2::ZERO), Sense::hover());
+        let painter = ui.painter();
+
+        for (row_idx, row) in self.rows.iter().enumerate() {
+            for (col_idx, key) in row.iter().enumerate() {
+                let key = match key {
+                    Some(v) => v,
+                    None => continue,
+                };
+
+                let min = rect.min + Vec2::new(col_idx as f32 * stride, row_idx as f32 * stride);
+                let cell_rect = Rect::from_min_size(min, Vec2::splat(CELL_SIZE));
+
+                let held = held_keys.get(&key.code);
+                let heat = self.heatmap.as_ref().and_then(|h| h.color(key.code));
+                let (fill, text_color) = match (held, heat) {
+                    (Some(_), _) => (theme.text, theme.background),
+                    (None, Some(heat)) => (heat, theme.text),
+                    (None, None) => (theme.text.gamma_multiply(0.15), theme.text),
+                };
+
+                painter.rect_filled(cell_rect, 4.0, fill);
+
+                if let (true, Some(held)) = (self.hold_bars, held) {
+                    let held_for = held.held_for(now);
+                    let fraction = (held_for.as_secs_f32() / HOLD_BAR_FULL.as_secs_f32()).min(1.0);
+                    let bar_rect = Rect::from_min_size(
+                        cell_rect.left_bottom() - Vec2::new(0.0, 3.0),
+                        Vec2::new(CELL_SIZE * fraction, 3.0),
+                    );
+                    painter.rect_filled(bar_rect, 0.0, Color32::from_rgb(255, 140, 0));
+                }
+
+                painter.text(
+                    cell_rect.center(),
+                    Align2::CENTER_CENTER,
+                    &key.label,
+                    FontId::monospace(14.0),
+                    text_color,
+                );
+            }
+        }
+
+        if let (Some(frame_rate), Some((label, held_for))) = (self.frame_rate, &self.last_release) {
+            let frames = (held_for.as_secs_f32() * frame_rate).round() as u32;
+            let text = format!("{label} {frames}f ({}ms)", held_for.as_millis());
+            let label_text = RichText::new(text)
+                .family(FontFamily::Monospace)
+                .color(theme.text)
+                .size(15.0);
+
+            ui.label(label_text);
+        }
+    }
+}
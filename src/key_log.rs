@@ -0,0 +1,60 @@
+use std::{
+    fs::{File, OpenOptions},
+    io::{Error as IoError, LineWriter, Write},
+    path::Path,
+    time::Duration,
+};
+
+use super::{stats::json_string, symbols::Symbols, Modifiers};
+
+#[derive(Debug)]
+pub enum KeyLogError {
+    Open(IoError),
+    Write(IoError),
+}
+
+// Appends every key press to a JSON lines file, e.g.
+// {"key": "c", "modifiers": ["Ctrl"], "timestamp": 1717171717.503114}
+// Timestamps are seconds since the Unix epoch, from the evdev event or from when X11 and replayed
+// events were received
+pub struct KeyLog {
+    out: LineWriter<File>,
+}
+
+impl KeyLog {
+    pub fn open(path: &Path) -> Result<KeyLog, KeyLogError> {
+        let out = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map_err(KeyLogError::Open)?;
+
+        Ok(KeyLog {
+            out: LineWriter::new(out),
+        })
+    }
+
+    pub fn write(
+        &mut self,
+        key_s: &str,
+        modifiers: &Modifiers,
+        timestamp: Duration,
+    ) -> Result<(), KeyLogError> {
+        let symbols = Symbols::plain();
+        let mut modifiers = symbols.chord_keys(modifiers, key_s);
+        modifiers.pop();
+        let modifiers = modifiers
+            .into_iter()
+            .map(json_string)
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        writeln!(
+            self.out,
+            "{{\"key\": {}, \"modifiers\": [{modifiers}], \"timestamp\": {:.6}}}",
+            json_string(key_s),
+            timestamp.as_secs_f64()
+        )
+        .map_err(KeyLogError::Write)
+    }
+}
@@ -0,0 +1,69 @@
+use std::{
+    io::Error as IoError,
+    os::unix::ffi::OsStrExt,
+    path::{Path, PathBuf},
+    sync::mpsc::{Receiver, Sender},
+    thread,
+};
+
+use eframe::egui;
+
+use super::{inotify::Inotify, ipc::IpcCommand, xkbcommon::KeymapSource};
+
+#[derive(Debug)]
+pub enum WatchError {
+    Init(IoError),
+    AddWatch(IoError),
+}
+
+// Rebuilds the keymap whenever the mapping file changes, for setups where a script regenerates
+// it on layout switches
+pub fn spawn_watcher(
+    path: &Path,
+    tx: Sender<IpcCommand>,
+    rx: Receiver<egui::Context>,
+) -> Result<(), WatchError> {
+    let inotify = Inotify::new().map_err(WatchError::Init)?;
+
+    // Watch the directory, scripts usually replace the file rather than writing to it in place
+    let dir = match path.parent() {
+        Some(p) if !p.as_os_str().is_empty() => p,
+        _ => Path::new("."),
+    };
+    inotify
+        .add_watch(dir, libc::IN_CLOSE_WRITE | libc::IN_MOVED_TO)
+        .map_err(WatchError::AddWatch)?;
+
+    let path = path.to_path_buf();
+    thread::spawn(move || watcher_thread(inotify, path, tx, rx));
+    Ok(())
+}
+
+fn watcher_thread(
+    mut inotify: Inotify,
+    path: PathBuf,
+    tx: Sender<IpcCommand>,
+    rx: Receiver<egui::Context>,
+) {
+    let ctx = rx.recv().unwrap();
+    let file_name = path.file_name().map(|n| n.as_bytes().to_vec());
+
+    loop {
+        let names = match inotify.read_names() {
+            Ok(v) => v,
+            Err(_) => return,
+        };
+
+        if !names.iter().any(|name| Some(name) == file_name.as_ref()) {
+            continue;
+        }
+
+        if tx
+            .send(IpcCommand::SetKeymap(KeymapSource::File(path.clone())))
+            .is_err()
+        {
+            return;
+        }
+        ctx.request_repaint();
+    }
+}
@@ -1,18 +1,79 @@
 use std::{
-    collections::VecDeque,
-    fs::File,
+    collections::{hash_map::DefaultHasher, BTreeMap, BTreeSet, VecDeque},
+    fs::{self, File},
+    hash::{Hash, Hasher},
     io::Read,
     mem::MaybeUninit,
     path::PathBuf,
-    sync::mpsc::{self, Receiver, Sender},
+    sync::{
+        mpsc::{self, Receiver, Sender},
+        Arc,
+    },
     thread,
+    time::{Duration, Instant, SystemTime},
 };
 
+use anchor::Anchor;
+use chord::Chord;
+use chord_timing::ChordTimings;
+use clipboard::PasteChannels;
+use clock::Clock;
 use eframe::egui;
 use egui::{FontFamily, RichText};
-use xkbcommon::Xkb;
+use evemu::EvemuRecorder;
+use frame_export::FrameExporter;
+use health::{DeviceHealth, SharedHealth};
+use hotplug::ClaimedDevices;
+use ipc::IpcCommand;
+use key_filter::KeyFilter;
+use key_grid::KeyGrid;
+use key_log::KeyLog;
+use mouse::MouseInput;
+use reactions::Reactions;
+use ripple::Ripples;
+use segments::Segments;
+use special_chords::{SpecialAction, SpecialChords};
+use stats::Stats;
+use symbols::Symbols;
+use theme::Theme;
+use toast::{Toast, Toasts};
+use xkbcommon::{KeymapSource, RuleNames, Xkb};
 
+mod anchor;
+mod chord;
+mod chord_timing;
+mod clipboard;
+mod clock;
+mod compositor;
+mod config;
+mod discovery;
+mod evdev;
+mod evemu;
+mod focus;
+mod frame_export;
+mod health;
+mod hotplug;
+mod inotify;
 mod input_bindings;
+mod ipc;
+mod key_filter;
+mod key_grid;
+mod key_log;
+mod keymap_watch;
+mod monitors;
+mod mouse;
+mod mqtt;
+mod reactions;
+mod ripple;
+mod segments;
+mod special_chords;
+mod speech;
+mod stats;
+mod symbols;
+mod theme;
+mod toast;
+mod wayland_keymap;
+mod x11_record;
 mod xkbcommon;
 
 // https://docs.kernel.org/input/input.html
@@ -26,36 +87,680 @@ enum KeyPressState {
     Down = 1,
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug, Eq, PartialEq)]
 pub enum KeyPress {
     Ctrl,
     Alt,
     Shift,
     Super,
+    Mouse(MouseInput),
     Other(String),
 }
 
+impl KeyPress {
+    fn is_modifier(&self) -> bool {
+        matches!(
+            self,
+            KeyPress::Ctrl | KeyPress::Alt | KeyPress::Shift | KeyPress::Super
+        )
+    }
+
+    fn display_name(&self) -> &str {
+        match self {
+            KeyPress::Ctrl => "Ctrl",
+            KeyPress::Alt => "Alt",
+            KeyPress::Shift => "Shift",
+            KeyPress::Super => "Super",
+            KeyPress::Mouse(m) => m.label(),
+            KeyPress::Other(s) => s,
+        }
+    }
+}
+
 #[derive(Debug)]
 enum ArgParseError {
-    EventInputMissing,
+    InvalidModifierTimeout,
+    InvalidModifierGrace,
+    InvalidChordWindow,
+    InvalidExpansionGap,
+    InvalidMaxFps,
+    InvalidSilenceIndicator,
+    InvalidChord(String),
+    InvalidAnnotation(String),
+    InvalidReaction(String),
+    InvalidStatsAlias(String),
+    CaptureOnDemandWithoutSocket,
+    StartHiddenWithoutSocket,
+    InvalidDpad,
+    InvalidPreset,
+    InvalidMode,
+    InvalidRepeatMode,
+    InvalidKeyGrid,
+    InvalidHeatmapColors,
+    HeatmapWithoutGrid,
+    ChordTimingWithoutStats,
+    StatsAliasWithoutStats,
+    InvalidFrameRate,
+    PersistenceDisabled,
+    ConflictingInputs,
+    ConflictingKeymaps,
+    InvalidBackend,
+    InvalidShowOnHold,
+    RecordingWithoutDevice,
+    RecordingMultipleDevices,
+    ExportWithoutReplay,
+    InvalidFontSize,
+    InvalidFont,
+    InvalidMaxLines,
+    InvalidColumns,
+    ConflictingLayouts,
+    InvalidAnchor,
+    InvalidMargin,
+    InvalidOffset,
+    InvalidPanelAlpha,
+    InvalidColor,
+    InvalidTheme(String),
+    InvalidKeyFilter,
+    InvalidSpeechInterval,
+    InvalidFadeAfter,
+    InvalidHoldDuration,
+    InvalidPasteLength,
+    InvalidTransition,
+    InvalidToastDuration,
+    InvalidSymbol,
+}
+
+enum InputSource {
+    // Use the keyboards in /dev/input
+    Discover,
+    Devices(Vec<PathBuf>),
+    // evemu or libinput-record file
+    Replay(PathBuf),
+    // XRecord, for X11 sessions without access to /dev/input
+    X11,
+}
+
+#[derive(Eq, PartialEq)]
+enum Mode {
+    History,
+    KroTest,
+    // Full keyboard that lights up held keys instead of the history
+    Keyboard,
+}
+
+// What to do with autorepeat of a held key
+#[derive(Clone, Copy, Eq, PartialEq)]
+enum RepeatMode {
+    Ignore,
+    // Each repeat counts as another press
+    Count,
+    // Mark the item as held
+    Indicate,
 }
 
 struct Args {
-    event_input_path: PathBuf,
+    mode: Mode,
+    input_source: InputSource,
+    show_on_hold: Option<KeyPress>,
+    modifier_timeout: Option<Duration>,
+    modifier_grace: Option<Duration>,
+    chord_window: Option<Duration>,
+    expansion_gap: Option<Duration>,
+    chord_timing: bool,
+    silence_indicator: Option<Duration>,
+    session_code: Option<String>,
+    special_chords: Vec<(Chord, SpecialAction)>,
+    clipboard_counter: bool,
+    paste_length: Option<usize>,
+    control_socket: Option<PathBuf>,
+    capture_on_demand: bool,
+    start_hidden: bool,
+    no_persist: bool,
+    dpad: Option<KeyGrid>,
+    chords_only: bool,
+    repeat_mode: RepeatMode,
+    mouse: bool,
+    click_ripple: bool,
+    held_keys_hud: bool,
+    keycaps: bool,
+    key_grid: Option<KeyGrid>,
+    heatmap_reset_chord: Option<Chord>,
+    health_chord: Option<Chord>,
+    segment_chord: Option<Chord>,
+    segment_dir: Option<PathBuf>,
+    stats_out: Option<PathBuf>,
+    // Counted under their name in the stats, e.g. Ctrl+c as copy
+    stats_aliases: Vec<(Chord, String)>,
+    log_keys: Option<PathBuf>,
+    hold_timers: bool,
+    hold_duration: Option<Duration>,
+    bad_chords: Vec<Chord>,
+    annotations: Vec<(Chord, String)>,
+    reactions: Vec<(Chord, String)>,
+    record_evemu: Option<PathBuf>,
+    toast_duration: Duration,
+    keymap_source: Option<KeymapSource>,
+    wayland_keymap: bool,
+    export_frames: Option<PathBuf>,
+    frame_rate: f32,
+    max_fps: Option<f32>,
+    font_size: f32,
+    // Contents of --font-path
+    font: Option<Vec<u8>>,
+    max_lines: usize,
+    columns: usize,
+    horizontal: bool,
+    symbols: Symbols,
+    anchor: Option<Anchor>,
+    monitor: Option<String>,
+    // Resolved from monitor in main
+    monitor_rect: Option<egui::Rect>,
+    margin: f32,
+    offset: egui::Vec2,
+    opaque: bool,
+    theme: Theme,
+    speech_filter: Option<KeyFilter>,
+    speech_interval: Duration,
+    fade_after: Option<Duration>,
+    transition: Option<Duration>,
+    app_sections: bool,
+    interactive: bool,
+    mqtt_broker: Option<String>,
+    mqtt_topic: String,
+    mqtt_filter: KeyFilter,
 }
 
 impl Args {
-    fn try_parse<It: Iterator<Item = String>>(mut arg_it: It) -> Result<Args, ArgParseError> {
+    fn try_parse<It: Iterator<Item = String>>(arg_it: It) -> Result<Args, ArgParseError> {
+        let mut arg_it = arg_it.peekable();
+
         // Skip program name
         let _ = arg_it.next();
 
-        let mut event_input_path = None;
+        let mut mode = match arg_it.peek().map(String::as_str) {
+            Some("kro-test") => {
+                let _ = arg_it.next();
+                Mode::KroTest
+            }
+            _ => Mode::History,
+        };
+
+        let mut event_input_paths = Vec::new();
+        let mut replay = None;
+        let mut show_on_hold = None;
+        let mut modifier_timeout = None;
+        let mut modifier_grace = None;
+        let mut chord_window = None;
+        let mut expansion_gap = None;
+        let mut chord_timing = false;
+        let mut silence_indicator = None;
+        let mut watermark = false;
+        let mut special_chords = Vec::new();
+        let mut clipboard_counter = false;
+        let mut paste_length = None;
+        let mut control_socket = None;
+        let mut capture_on_demand = false;
+        let mut start_hidden = false;
+        let mut no_persist = false;
+        let mut dpad = None;
+        let mut chords_only = false;
+        let mut repeat_mode = RepeatMode::Ignore;
+        let mut mouse = false;
+        let mut click_ripple = false;
+        let mut held_keys_hud = false;
+        let mut keycaps = false;
+        let mut key_grid = None;
+        let mut heatmap = None;
+        let mut heatmap_reset_chord = None;
+        let mut health_chord = None;
+        let mut segment_chord = None;
+        let mut segment_dir = None;
+        let mut stats_out = None;
+        let mut stats_aliases = Vec::new();
+        let mut log_keys = None;
+        let mut frame_rate = 60.0;
+        let mut max_fps = None;
+        let mut hold_timers = false;
+        let mut hold_duration = None;
+        let mut bad_chords = Vec::new();
+        let mut annotations = Vec::new();
+        let mut reactions = Vec::new();
+        let mut record_evemu = None;
+        let mut toast_duration = Duration::from_secs(5);
+        let mut xkb_mapping = None;
+        let mut rule_names = None::<RuleNames>;
+        let mut wayland_keymap = false;
+        let mut export_frames = None;
+        let mut font_size = 15.0;
+        let mut font = None;
+        let mut max_lines = MAX_LINES;
+        let mut columns = 1;
+        let mut horizontal = false;
+        let mut glyphs = false;
+        let mut symbol_overrides = Vec::new();
+        let mut anchor = None;
+        let mut monitor = None;
+        let mut margin = 0.0;
+        let mut offset = egui::Vec2::ZERO;
+        let mut theme = "dark".to_string();
+        let mut panel_alpha = None;
+        let mut opaque = false;
+        let mut text_color = None;
+        let mut theme_background = None;
+        let mut theme_pill = None;
+        let mut theme_border = None;
+        let mut theme_shadow = None;
+        let mut speak = false;
+        let mut speech_filter = KeyFilter::Chords;
+        let mut speech_interval = Duration::from_millis(500);
+        let mut fade_after = None;
+        let mut transition = None;
+        let mut reduced_motion = false;
+        let mut app_sections = false;
+        let mut interactive = false;
+        let mut mqtt_broker = None;
+        let mut mqtt_topic = "keyboard-overlay/chords".to_string();
+        let mut mqtt_filter = KeyFilter::Chords;
+        let mut x11_backend = false;
 
         while let Some(arg) = arg_it.next() {
             match arg.as_str() {
-                "--event-input-path" => {
-                    event_input_path = arg_it.next().map(Into::into);
+                // --keyboard-devices lets the config name the halves of a split keyboard in its
+                // [keyboard] table. All devices share one keymap state, so they act as one keyboard
+                "--event-input-path" | "--keyboard-devices" => {
+                    if let Some(path) = arg_it.next() {
+                        event_input_paths.push(path.into());
+                    }
+                }
+                "--mode" => {
+                    mode = match arg_it.next().as_deref() {
+                        Some("history") => Mode::History,
+                        Some("keyboard") => Mode::Keyboard,
+                        _ => return Err(ArgParseError::InvalidMode),
+                    };
+                }
+                "--replay" => {
+                    replay = arg_it.next().map(Into::into);
+                }
+                "--backend" => {
+                    x11_backend = match arg_it.next().as_deref() {
+                        Some("evdev") => false,
+                        Some("x11") => true,
+                        _ => return Err(ArgParseError::InvalidBackend),
+                    };
+                }
+                "--show-on-hold" => {
+                    show_on_hold = Some(
+                        arg_it
+                            .next()
+                            .and_then(|s| parse_hold_key(&s))
+                            .ok_or(ArgParseError::InvalidShowOnHold)?,
+                    );
+                }
+                "--modifier-timeout" => {
+                    let duration =
+                        parse_secs(arg_it.next(), ArgParseError::InvalidModifierTimeout)?;
+                    modifier_timeout = Some(duration);
+                }
+                "--modifier-grace" => {
+                    let duration = parse_secs(arg_it.next(), ArgParseError::InvalidModifierGrace)?;
+                    modifier_grace = Some(duration);
+                }
+                "--chord-window" => {
+                    let duration = parse_secs(arg_it.next(), ArgParseError::InvalidChordWindow)?;
+                    chord_window = Some(duration);
+                }
+                "--collapse-expansions" => {
+                    let duration = parse_secs(arg_it.next(), ArgParseError::InvalidExpansionGap)?;
+                    expansion_gap = Some(duration);
+                }
+                "--chord-timing" => {
+                    chord_timing = true;
+                }
+                "--silence-indicator" => {
+                    let duration =
+                        parse_secs(arg_it.next(), ArgParseError::InvalidSilenceIndicator)?;
+                    silence_indicator = Some(duration);
+                }
+                "--watermark" => {
+                    watermark = true;
+                }
+                "--undo-chord" => {
+                    special_chords.push((parse_chord(arg_it.next())?, SpecialAction::Undo));
+                }
+                "--redo-chord" => {
+                    special_chords.push((parse_chord(arg_it.next())?, SpecialAction::Redo));
+                }
+                "--cut-chord" => {
+                    special_chords.push((parse_chord(arg_it.next())?, SpecialAction::Cut));
+                }
+                "--copy-chord" => {
+                    special_chords.push((parse_chord(arg_it.next())?, SpecialAction::Copy));
+                }
+                "--paste-chord" => {
+                    special_chords.push((parse_chord(arg_it.next())?, SpecialAction::Paste));
+                }
+                "--clipboard-counter" => {
+                    clipboard_counter = true;
+                }
+                "--paste-length" => {
+                    let min_chars = arg_it
+                        .next()
+                        .and_then(|s| s.parse::<usize>().ok())
+                        .ok_or(ArgParseError::InvalidPasteLength)?;
+                    paste_length = Some(min_chars);
+                }
+                "--control-socket" => {
+                    control_socket = arg_it.next().map(Into::into);
+                }
+                "--capture-on-demand" => {
+                    capture_on_demand = true;
+                }
+                "--start-hidden" => {
+                    start_hidden = true;
+                }
+                "--no-persist" => {
+                    no_persist = true;
+                }
+                "--dpad" => {
+                    let grid = match arg_it.next().as_deref() {
+                        Some("arrows") => KeyGrid::dpad_arrows(),
+                        Some("wasd") => KeyGrid::dpad_wasd(),
+                        _ => return Err(ArgParseError::InvalidDpad),
+                    };
+                    dpad = Some(grid);
+                }
+                "--chords-only" => {
+                    chords_only = true;
+                }
+                "--repeat" => {
+                    repeat_mode = match arg_it.next().as_deref() {
+                        Some("ignore") => RepeatMode::Ignore,
+                        Some("count") => RepeatMode::Count,
+                        Some("indicate") => RepeatMode::Indicate,
+                        _ => return Err(ArgParseError::InvalidRepeatMode),
+                    };
+                }
+                "--mouse" => {
+                    mouse = true;
+                }
+                "--click-ripple" => {
+                    click_ripple = true;
+                }
+                "--held-keys" => {
+                    held_keys_hud = true;
+                }
+                "--keycaps" => {
+                    keycaps = true;
+                }
+                "--key-grid" => {
+                    let grid = arg_it
+                        .next()
+                        .and_then(|s| KeyGrid::parse(&s))
+                        .ok_or(ArgParseError::InvalidKeyGrid)?;
+                    key_grid = Some(grid);
+                }
+                "--heatmap" => {
+                    heatmap = Some((
+                        egui::Color32::from_rgb(40, 40, 160),
+                        egui::Color32::from_rgb(220, 40, 40),
+                    ));
+                }
+                "--heatmap-colors" => {
+                    let colors = arg_it
+                        .next()
+                        .and_then(|s| {
+                            let (cold, hot) = s.split_once(',')?;
+                            Some((parse_color(cold)?, parse_color(hot)?))
+                        })
+                        .ok_or(ArgParseError::InvalidHeatmapColors)?;
+                    heatmap = Some(colors);
+                }
+                "--heatmap-reset-chord" => {
+                    heatmap_reset_chord = Some(parse_chord(arg_it.next())?);
+                }
+                "--health-chord" => {
+                    health_chord = Some(parse_chord(arg_it.next())?);
+                }
+                "--segment-chord" => {
+                    segment_chord = Some(parse_chord(arg_it.next())?);
+                }
+                "--segment-dir" => {
+                    segment_dir = arg_it.next().map(Into::into);
+                }
+                "--stats-out" => {
+                    stats_out = arg_it.next().map(Into::into);
+                }
+                "--stats-alias" => {
+                    let s = arg_it.next().unwrap_or_default();
+                    let alias = split_chord_text(&s).ok_or(ArgParseError::InvalidStatsAlias(s))?;
+                    stats_aliases.push(alias);
+                }
+                "--log-keys" => {
+                    log_keys = arg_it.next().map(Into::into);
+                }
+                "--frame-rate" => {
+                    frame_rate = parse_rate(arg_it.next(), ArgParseError::InvalidFrameRate)?;
+                }
+                "--max-fps" => {
+                    max_fps = Some(parse_rate(arg_it.next(), ArgParseError::InvalidMaxFps)?);
+                }
+                "--hold-timers" => {
+                    hold_timers = true;
+                }
+                "--hold-duration" => {
+                    let duration = parse_secs(arg_it.next(), ArgParseError::InvalidHoldDuration)?;
+                    hold_duration = Some(duration);
+                }
+                "--bad-chord" => {
+                    bad_chords.push(parse_chord(arg_it.next())?);
+                }
+                "--annotate" => {
+                    annotations.push(parse_annotation(arg_it.next())?);
+                }
+                "--react" => {
+                    let s = arg_it.next().unwrap_or_default();
+                    let reaction = split_chord_text(&s).ok_or(ArgParseError::InvalidReaction(s))?;
+                    reactions.push(reaction);
                 }
+                "--record-evemu" => {
+                    record_evemu = arg_it.next().map(Into::into);
+                }
+                "--toast-duration" => {
+                    let duration = parse_secs(arg_it.next(), ArgParseError::InvalidToastDuration)?;
+                    toast_duration = duration;
+                }
+                "--xkb-mapping" => {
+                    xkb_mapping = arg_it.next().map(Into::into);
+                }
+                "--wayland-keymap" => {
+                    wayland_keymap = true;
+                }
+                "--layout" => {
+                    rule_names.get_or_insert_with(Default::default).layout = arg_it.next();
+                }
+                "--variant" => {
+                    rule_names.get_or_insert_with(Default::default).variant = arg_it.next();
+                }
+                "--options" => {
+                    rule_names.get_or_insert_with(Default::default).options = arg_it.next();
+                }
+                "--model" => {
+                    rule_names.get_or_insert_with(Default::default).model = arg_it.next();
+                }
+                "--export-frames" => {
+                    export_frames = arg_it.next().map(Into::into);
+                }
+                "--font-size" => {
+                    font_size = arg_it
+                        .next()
+                        .and_then(|s| s.parse::<f32>().ok())
+                        .filter(|v| *v > 0.0)
+                        .ok_or(ArgParseError::InvalidFontSize)?;
+                }
+                "--font-path" => {
+                    let data = arg_it
+                        .next()
+                        .and_then(|path| fs::read(path).ok())
+                        .ok_or(ArgParseError::InvalidFont)?;
+                    font = Some(data);
+                }
+                "--max-lines" => {
+                    max_lines = arg_it
+                        .next()
+                        .and_then(|s| s.parse::<usize>().ok())
+                        .filter(|v| *v > 0)
+                        .ok_or(ArgParseError::InvalidMaxLines)?;
+                }
+                "--columns" => {
+                    columns = arg_it
+                        .next()
+                        .and_then(|s| s.parse::<usize>().ok())
+                        .filter(|v| *v > 0)
+                        .ok_or(ArgParseError::InvalidColumns)?;
+                }
+                "--opaque" => {
+                    opaque = true;
+                }
+                "--horizontal" => {
+                    horizontal = true;
+                }
+                "--symbols" => {
+                    glyphs = true;
+                }
+                "--symbol" => {
+                    let entry = arg_it
+                        .next()
+                        .as_deref()
+                        .and_then(symbols::parse_entry)
+                        .ok_or(ArgParseError::InvalidSymbol)?;
+                    symbol_overrides.push(entry);
+                }
+                "--anchor" => {
+                    anchor = Some(
+                        arg_it
+                            .next()
+                            .and_then(|s| Anchor::parse(&s))
+                            .ok_or(ArgParseError::InvalidAnchor)?,
+                    );
+                }
+                "--monitor" => {
+                    monitor = arg_it.next();
+                }
+                "--margin" => {
+                    margin = arg_it
+                        .next()
+                        .and_then(|s| s.parse::<f32>().ok())
+                        .filter(|v| v.is_finite() && *v >= 0.0)
+                        .ok_or(ArgParseError::InvalidMargin)?;
+                }
+                "--offset" => {
+                    offset = arg_it
+                        .next()
+                        .and_then(|s| {
+                            let (x, y) = s.split_once(',')?;
+                            Some(egui::vec2(x.parse().ok()?, y.parse().ok()?))
+                        })
+                        // Negative moves up or left, e.g. 0,-40
+                        .filter(|v| v.x.is_finite() && v.y.is_finite())
+                        .ok_or(ArgParseError::InvalidOffset)?;
+                }
+                // --theme-preset lets the config set the preset in its [theme] table
+                "--theme" | "--theme-preset" => {
+                    theme = arg_it.next().unwrap_or_default();
+                }
+                "--panel-alpha" => {
+                    let alpha = arg_it
+                        .next()
+                        .and_then(|s| s.parse::<u8>().ok())
+                        .ok_or(ArgParseError::InvalidPanelAlpha)?;
+                    panel_alpha = Some(alpha);
+                }
+                "--text-color" | "--theme-text" => {
+                    let color = arg_it
+                        .next()
+                        .and_then(|s| parse_color(&s))
+                        .ok_or(ArgParseError::InvalidColor)?;
+                    text_color = Some(color);
+                }
+                "--theme-background" => {
+                    let color = arg_it
+                        .next()
+                        .and_then(|s| parse_color(&s))
+                        .ok_or(ArgParseError::InvalidColor)?;
+                    theme_background = Some(color);
+                }
+                "--theme-pill" => {
+                    theme_pill = Some(parse_optional_color(arg_it.next())?);
+                }
+                "--theme-border" => {
+                    theme_border = Some(parse_optional_color(arg_it.next())?);
+                }
+                "--theme-shadow" => {
+                    theme_shadow = Some(parse_optional_color(arg_it.next())?);
+                }
+                "--speak" => {
+                    speak = true;
+                }
+                "--speak-filter" => {
+                    speech_filter = arg_it
+                        .next()
+                        .and_then(|s| KeyFilter::parse(&s))
+                        .ok_or(ArgParseError::InvalidKeyFilter)?;
+                }
+                "--speak-interval" => {
+                    let duration = parse_secs(arg_it.next(), ArgParseError::InvalidSpeechInterval)?;
+                    speech_interval = duration;
+                }
+                "--fade-after" => {
+                    let duration = parse_secs(arg_it.next(), ArgParseError::InvalidFadeAfter)?;
+                    if duration.is_zero() {
+                        return Err(ArgParseError::InvalidFadeAfter);
+                    }
+                    fade_after = Some(duration);
+                }
+                "--transition" => {
+                    let duration = parse_secs(arg_it.next(), ArgParseError::InvalidTransition)?;
+                    if duration.is_zero() {
+                        return Err(ArgParseError::InvalidTransition);
+                    }
+                    transition = Some(duration);
+                }
+                "--reduced-motion" => {
+                    reduced_motion = true;
+                }
+                "--interactive" => {
+                    interactive = true;
+                }
+                "--app-sections" => {
+                    app_sections = true;
+                }
+                "--mqtt" => {
+                    mqtt_broker = arg_it.next();
+                }
+                "--mqtt-topic" => {
+                    if let Some(topic) = arg_it.next() {
+                        mqtt_topic = topic;
+                    }
+                }
+                "--mqtt-filter" => {
+                    mqtt_filter = arg_it
+                        .next()
+                        .and_then(|s| KeyFilter::parse(&s))
+                        .ok_or(ArgParseError::InvalidKeyFilter)?;
+                }
+                "--config" => {
+                    // Already merged in by config::merge_args
+                    let _ = arg_it.next();
+                }
+                "--preset" => match arg_it.next().as_deref() {
+                    Some("gaming") => {
+                        dpad = Some(KeyGrid::dpad_wasd());
+                        chords_only = true;
+                        held_keys_hud = true;
+                    }
+                    _ => return Err(ArgParseError::InvalidPreset),
+                },
                 "--help" => {
                     println!("{}", Args::help());
                     std::process::exit(1);
@@ -68,266 +773,2732 @@ impl Args {
             }
         }
 
-        let event_input_path = event_input_path.ok_or(ArgParseError::EventInputMissing)?;
+        let input_source = match (event_input_paths.is_empty(), replay, x11_backend) {
+            (false, None, false) => InputSource::Devices(event_input_paths),
+            (true, Some(path), false) => InputSource::Replay(path),
+            (true, None, false) => InputSource::Discover,
+            (true, None, true) => InputSource::X11,
+            _ => return Err(ArgParseError::ConflictingInputs),
+        };
+
+        if capture_on_demand && control_socket.is_none() {
+            return Err(ArgParseError::CaptureOnDemandWithoutSocket);
+        }
+
+        if start_hidden && control_socket.is_none() {
+            return Err(ArgParseError::StartHiddenWithoutSocket);
+        }
+
+        if no_persist
+            && (record_evemu.is_some()
+                || export_frames.is_some()
+                || mqtt_broker.is_some()
+                || segment_dir.is_some()
+                || stats_out.is_some()
+                || log_keys.is_some())
+        {
+            return Err(ArgParseError::PersistenceDisabled);
+        }
+
+        // Recordings describe the device they came from, replays and X11 don't have one
+        if matches!(input_source, InputSource::Replay(_) | InputSource::X11)
+            && record_evemu.is_some()
+        {
+            return Err(ArgParseError::RecordingWithoutDevice);
+        }
+
+        // The slowest shortcuts are reported in the stats file
+        if chord_timing && stats_out.is_none() {
+            return Err(ArgParseError::ChordTimingWithoutStats);
+        }
+
+        if !stats_aliases.is_empty() && stats_out.is_none() {
+            return Err(ArgParseError::StatsAliasWithoutStats);
+        }
+
+        if export_frames.is_some() && !matches!(input_source, InputSource::Replay(_)) {
+            return Err(ArgParseError::ExportWithoutReplay);
+        }
+
+        // An evemu recording only describes a single device
+        if matches!(&input_source, InputSource::Devices(paths) if paths.len() > 1)
+            && record_evemu.is_some()
+        {
+            return Err(ArgParseError::RecordingMultipleDevices);
+        }
+
+        let keymap_source = match (xkb_mapping, rule_names) {
+            (Some(path), None) => Some(KeymapSource::File(path)),
+            (None, Some(names)) => Some(KeymapSource::Names(names)),
+            (None, None) => None,
+            (Some(_), Some(_)) => return Err(ArgParseError::ConflictingKeymaps),
+        };
+
+        if wayland_keymap && keymap_source.is_some() {
+            return Err(ArgParseError::ConflictingKeymaps);
+        }
+
+        if matches!(mode, Mode::Keyboard | Mode::KroTest) && key_grid.is_none() {
+            key_grid = Some(KeyGrid::keyboard());
+        }
+
+        // Wins over the options it turns off, so it can be set once in the config
+        if reduced_motion {
+            click_ripple = false;
+            reactions.clear();
+            transition = None;
+        }
+
+        if let Some((cold, hot)) = heatmap {
+            key_grid = Some(
+                key_grid
+                    .ok_or(ArgParseError::HeatmapWithoutGrid)?
+                    .with_heatmap(cold, hot),
+            );
+        }
+
+        if horizontal && columns > 1 {
+            return Err(ArgParseError::ConflictingLayouts);
+        }
+
+        let mut symbols = match glyphs {
+            true => Symbols::glyphs(),
+            false => Symbols::plain(),
+        };
+        for (name, display) in symbol_overrides {
+            symbols.insert(name, display);
+        }
+
+        // Placing the window on a monitor needs a corner to put it in
+        if monitor.is_some() && anchor.is_none() {
+            anchor = Some(Anchor::BottomLeft);
+        }
+
+        if hold_timers {
+            dpad = dpad.map(KeyGrid::with_hold_bars);
+            key_grid = key_grid.map(KeyGrid::with_hold_bars);
+        }
+
+        // Overrides apply to whichever preset was picked, wherever they are on the command line
+        let mut theme = Theme::preset(&theme).ok_or(ArgParseError::InvalidTheme(theme))?;
+        if let Some(v) = text_color {
+            theme.text = v;
+        }
+        if let Some(v) = theme_background {
+            theme.background = v;
+        }
+        if let Some(v) = panel_alpha {
+            theme.background_alpha = v;
+        }
+        if let Some(v) = theme_pill {
+            theme.pill = v;
+        }
+        if let Some(v) = theme_border {
+            theme.border = v;
+        }
+        if let Some(v) = theme_shadow {
+            theme.shadow = v;
+        }
+
+        Ok(Args {
+            mode,
+            input_source,
+            show_on_hold,
+            modifier_timeout,
+            modifier_grace,
+            chord_window,
+            expansion_gap,
+            chord_timing,
+            silence_indicator,
+            session_code: watermark.then(generate_session_code),
+            special_chords,
+            clipboard_counter,
+            paste_length,
+            control_socket,
+            capture_on_demand,
+            start_hidden,
+            no_persist,
+            dpad,
+            chords_only,
+            repeat_mode,
+            mouse,
+            click_ripple,
+            held_keys_hud,
+            keycaps,
+            key_grid: key_grid.map(|g| g.with_timing(frame_rate)),
+            heatmap_reset_chord,
+            health_chord,
+            segment_chord,
+            segment_dir,
+            stats_out,
+            stats_aliases,
+            log_keys,
+            hold_timers,
+            hold_duration,
+            bad_chords,
+            annotations,
+            reactions,
+            record_evemu,
+            toast_duration,
+            keymap_source,
+            wayland_keymap,
+            export_frames,
+            frame_rate,
+            max_fps,
+            font_size,
+            font,
+            max_lines,
+            columns,
+            horizontal,
+            symbols,
+            anchor,
+            monitor,
+            monitor_rect: None,
+            margin,
+            offset,
+            opaque,
+            theme,
+            speech_filter: speak.then_some(speech_filter),
+            speech_interval,
+            fade_after,
+            transition,
+            app_sections,
+            interactive,
+            mqtt_broker,
+            mqtt_topic,
+            mqtt_filter,
+        })
+    }
+
+    fn parse<It: Iterator<Item = String>>(arg_it: It) -> Args {
+        match Self::try_parse(arg_it) {
+            Ok(v) => v,
+            Err(e) => {
+                println!("Argument parsing failed: {e:?}");
+                println!("{}", Args::help());
+                std::process::exit(1);
+            }
+        }
+    }
+
+    fn help() -> String {
+        "\n\
+            keyboard-overlay: Displays keys in an overlay\n\
+\n\
+            Usage: keyboard-overlay [kro-test|check-config] [args]\n\
+\n\
+            Subcommands:\n\
+            kro-test: Show a keyboard that lights up held keys (--key-grid to use your own), and the maximum number held at once\n\
+            check-config: Check the config file and args for errors without starting, exits with 1 if there are any\n\
+\n\
+            Args:\n\
+            --mode [history|keyboard]: Show the key history or a keyboard that lights up held keys (--key-grid to use your own), defaults to history\n\
+            --event-input-path [path]: Path to read keyboard events from, defaults to all keyboards in /dev/input (repeatable). Halves of a split keyboard can be named together in the config, e.g. devices = [...] under [keyboard]\n\
+            --replay [path]: Replay events from an evemu-record or libinput-record file instead of a device\n\
+            --backend [evdev|x11]: Read keys from /dev/input or from the X server with XRecord, defaults to evdev\n\
+            --show-on-hold [key]: Only show the overlay, with the held keys above the history, while key is held (ctrl, alt, shift, super or a key name)\n\
+            --modifier-timeout [seconds]: Clear modifiers that have been held this long with no other input\n\
+            --modifier-grace [seconds]: Modifiers released this shortly before a key still count towards its chord, e.g. 0.05 for KVM switches\n\
+            --chord-window [seconds]: Show keys pressed within this long of each other as one line, e.g. 0.05 to see w + d for diagonal movement\n\
+            --collapse-expansions [seconds]: Show bursts of keys typed less than this apart as one line, e.g. 0.005 for snippets typed by text expanders\n\
+            --chord-timing: Time chords from the first modifier to the key, and --chord-window groups from the first key to the last, and add the slowest often used ones to --stats-out\n\
+            --silence-indicator [seconds]: Warn when input stops this long, e.g. when a VM grabs the keyboard\n\
+            --watermark: Show a short per-session code in the corner of the overlay and in --stats-out, --segment-dir and --export-frames output\n\
+            --undo-chord [chord]: Chord to highlight as undo, e.g. Ctrl+z (repeatable)\n\
+            --redo-chord [chord]: Chord to highlight as redo, e.g. Ctrl+Shift+z (repeatable)\n\
+            --cut-chord [chord]: Chord to highlight as cut, e.g. Ctrl+x (repeatable)\n\
+            --copy-chord [chord]: Chord to highlight as copy, e.g. Ctrl+c (repeatable)\n\
+            --paste-chord [chord]: Chord to highlight as paste, e.g. Ctrl+v (repeatable)\n\
+            --clipboard-counter: Show how many cuts, copies and pastes happened this session\n\
+            --paste-length [n]: Show how many characters were pasted when it's at least n, read from the X11 clipboard\n\
+            --control-socket [path]: Listen for commands on a unix socket (start-capture, stop-capture, show, hide, set-keymap [xkb file|layout=us variant=...], replay-speed [factor], replay-seek [chord], segment [name])\n\
+            --capture-on-demand: Ignore all input until start-capture is received, e.g. from an OBS script\n\
+            --start-hidden: Record history but don't draw the overlay until show is received\n\
+            --no-persist: Never write keys to disk or network and refuse options that would\n\
+            --dpad [arrows|wasd]: Show a d-pad that lights up held movement keys\n\
+            --chords-only: Only add keys pressed together with a modifier to the history\n\
+            --repeat [ignore|count|indicate]: What to do while a key autorepeats, count adds each repeat to the xN counter and indicate marks the key as held, defaults to ignore\n\
+            --mouse: Show mouse clicks and scrolling in the history, and use mice found in /dev/input as well as keyboards\n\
+            --click-ripple: Draw an expanding ring around the X11 pointer on every click, with or without --mouse\n\
+            --held-keys: Show which keys are currently held\n\
+            --keycaps: Draw each key of a chord as a keycap instead of plain text, colored by the --theme pill and border\n\
+            --preset gaming: Same as --dpad wasd --chords-only --held-keys\n\
+            --key-grid [definition]: Input viewer grid of physical keys, e.g. \"_ w _ / a s d / lshift space\"\n\
+            --heatmap: Color --key-grid or --mode keyboard keys by how often they were pressed\n\
+            --heatmap-colors [#rrggbb,#rrggbb]: Colors for the least and most pressed keys of --heatmap\n\
+            --heatmap-reset-chord [chord]: Chord that clears the --heatmap, e.g. Ctrl+Alt+h\n\
+            --health-chord [chord]: Chord that toggles a panel with event rates and errors for each input device, e.g. Ctrl+Alt+d\n\
+            --segment-chord [chord]: Chord that starts a new numbered segment of the session with its own stats, named segments can be started with the segment command of --control-socket\n\
+            --segment-dir [dir]: Save the stats of each finished segment to this directory\n\
+            --stats-out [path]: Save key and chord counts of the session every minute and on exit, as CSV if path ends in .csv, JSON otherwise. Includes left vs right modifier use and same vs cross hand chords\n\
+            --stats-alias [chord=name]: Also count a chord under a name of its own in --stats-out, e.g. Ctrl+c=copy (repeatable, or a [stats-aliases] table in the config)\n\
+            --log-keys [path]: PRIVACY WARNING: saves everything typed, passwords included, as plain text. Appends each key press with its modifiers and timestamp to path as JSON lines\n\
+            --frame-rate [fps]: Frame rate used for --key-grid hold timings and --export-frames, defaults to 60\n\
+            --max-fps [fps]: Limit repaints while fading, hold timers and click ripples animate, e.g. 30 to save GPU time\n\
+            --hold-timers: Show how long keys have been held in --held-keys and key grids\n\
+            --hold-duration [seconds]: Show how long a key was held next to it in the history once it's held at least this long, e.g. 0.5\n\
+            --bad-chord [chord]: Show a reminder when this chord is used, e.g. Ctrl+t (repeatable)\n\
+            --annotate [chord=name]: Show what a chord does next to it, e.g. Super+1=Workspace 1 (repeatable, or an [annotations] table in the config)\n\
+            --react [chord=emoji]: Briefly show a large emoji in the middle of the overlay instead of the chord, e.g. F13=🎉 (repeatable, or a [reactions] table in the config)\n\
+            --record-evemu [path]: Record all raw device events to path in evemu format\n\
+            --xkb-mapping [path]: Load the keymap from an xkb file instead of the X server, reloaded when it changes\n\
+            --wayland-keymap: Use the keymap from the wayland compositor instead of the X server, following layout changes\n\
+            --layout [layout]: Build the keymap from xkb names instead of the X server, e.g. us or us,de\n\
+            --variant [variant]: xkb variant for --layout, e.g. dvorak\n\
+            --options [options]: xkb options for --layout, e.g. ctrl:nocaps\n\
+            --model [model]: xkb model for --layout, e.g. pc105\n\
+            --export-frames [dir]: Render a --replay at a fixed --frame-rate, saving every frame as a png\n\
+            --toast-duration [seconds]: How long status messages stay in the overlay, defaults to 5\n\
+            --font-size [size]: Text size, defaults to 15\n\
+            --font-path [file]: TTF or OTF font to use for all text, e.g. the bold face of your stream's font (or path under [font] in the config)\n\
+            --max-lines [n]: Maximum number of history lines, defaults to 40\n\
+            --columns [n]: Wrap the history into this many columns of --max-lines each, defaults to 1\n\
+            --horizontal: Show the history in a single row, newest on the right, e.g. along the bottom of the screen\n\
+            --symbols: Show modifiers and special keys as glyphs, e.g. ⌃ ⇧ ⏎ instead of Ctrl + Shift + Return\n\
+            --symbol [name=text]: Show a modifier or keysym as this text, e.g. Return=↵ or Super=Win (repeatable, or a [symbols] table in the config)\n\
+            --anchor [top-left|top-right|bottom-left|bottom-right|center]: Keep the window in this corner of the monitor, the newest key is shown nearest the edge\n\
+            --monitor [n|name]: Show the overlay on this monitor, numbered as in xrandr --listmonitors or by output name, e.g. HDMI-1. Prefer names in the config, the overlay moves with the monitor when it's reconnected or changes resolution\n\
+            --margin [points]: Distance between the --anchor corner and the monitor edges, scaled with the monitor like the text\n\
+            --offset [x,y]: Extra offset in points applied to the --anchor position, e.g. 0,-40 to clear a taskbar\n\
+            --theme [dark|light|transparent|high-contrast]: Colors of the overlay, defaults to dark. Override single colors with the options below or a [theme] table in the config\n\
+            --panel-alpha [0-255]: Opacity of the overlay background, defaults to the theme's\n\
+            --theme-background [#rrggbb]: Color of the overlay background\n\
+            --theme-pill [#rrggbb|none]: Background behind each history line\n\
+            --theme-border [#rrggbb|none]: Border around each history line\n\
+            --theme-shadow [#rrggbb|none]: Shadow under history text\n\
+            --opaque: Use a solid background instead of a transparent window, the default when no X11 compositor is running\n\
+            --text-color [#rrggbb]: Color of history text, also --theme-text\n\
+            --fade-after [seconds]: Fade out and remove history lines that haven't been pressed for this long\n\
+            --transition [seconds]: Slide the overlay in and out on show and hide instead of cutting\n\
+            --reduced-motion: Turn off click ripples, reactions and --transition, for photosensitive viewers\n\
+            --interactive: Accept mouse and keyboard input and show a box to search the history\n\
+            --app-sections: Start a new history section when the focused X11 application changes\n\
+            --speak: Read keys out loud with speech-dispatcher\n\
+            --speak-filter [all|chords|actions]: Which keys --speak reads out, defaults to chords\n\
+            --speak-interval [seconds]: Minimum time between announcements, defaults to 0.5\n\
+            --mqtt [host:port]: Publish chords to an MQTT broker\n\
+            --mqtt-topic [topic]: Topic for --mqtt, defaults to keyboard-overlay/chords\n\
+            --mqtt-filter [all|chords|actions]: Which keys --mqtt publishes, defaults to chords\n\
+            --config [path]: Read options from a toml file, e.g. font-size = 18 or reduced_motion = true, defaults to ~/.config/keyboard-overlay/config.toml. Options on the command line replace the file's, --no-[option] drops one from it\n\
+            --help: Show this help and exit\n\
+        "
+        .to_string()
+    }
+}
+
+// Seconds, e.g. 0.5. Negative, infinite and NaN values are rejected, as are ones too long for a
+// Duration
+fn parse_secs(arg: Option<String>, err: ArgParseError) -> Result<Duration, ArgParseError> {
+    arg.and_then(|s| s.parse::<f32>().ok())
+        .and_then(|secs| Duration::try_from_secs_f32(secs).ok())
+        .ok_or(err)
+}
+
+// Per second, e.g. a frame rate. Tiny rates would make the frame duration overflow, huge ones
+// round it down to nothing
+fn parse_rate(arg: Option<String>, err: ArgParseError) -> Result<f32, ArgParseError> {
+    arg.and_then(|s| s.parse::<f32>().ok())
+        .filter(|v| Duration::try_from_secs_f32(1.0 / v).is_ok_and(|d| !d.is_zero()))
+        .ok_or(err)
+}
+
+// Key names are keysym names like in chords, e.g. F13, and can't be blank
+fn parse_hold_key(s: &str) -> Option<KeyPress> {
+    let s = s.trim();
+    let keypress = match s.to_lowercase().as_str() {
+        "" => return None,
+        "ctrl" => KeyPress::Ctrl,
+        "alt" => KeyPress::Alt,
+        "shift" => KeyPress::Shift,
+        "super" => KeyPress::Super,
+        _ if s.contains(char::is_whitespace) => return None,
+        _ => KeyPress::Other(s.to_string()),
+    };
+    Some(keypress)
+}
+
+fn parse_chord(s: Option<String>) -> Result<Chord, ArgParseError> {
+    let s = s.unwrap_or_default();
+    Chord::parse(&s).ok_or(ArgParseError::InvalidChord(s))
+}
+
+// e.g. "Ctrl+c=Copy". The chord may itself end in =, e.g. "Ctrl+==Zoom in", so split at the
+// first = that leaves a valid chord
+fn parse_annotation(s: Option<String>) -> Result<(Chord, String), ArgParseError> {
+    let s = s.unwrap_or_default();
+    split_chord_text(&s).ok_or(ArgParseError::InvalidAnnotation(s))
+}
+
+// chord=text, the chord may itself contain '=', e.g. Ctrl+equal=Zoom in
+fn split_chord_text(s: &str) -> Option<(Chord, String)> {
+    s.match_indices('=').find_map(|(i, _)| {
+        let chord = Chord::parse(&s[..i])?;
+        let text = s[i + 1..].trim();
+        (!text.is_empty()).then(|| (chord, text.to_string()))
+    })
+}
+
+// e.g. "#ff8800"
+fn parse_color(s: &str) -> Option<egui::Color32> {
+    let hex = s.strip_prefix('#')?;
+    if hex.len() != 6 {
+        return None;
+    }
+
+    let channel = |i: usize| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok();
+    Some(egui::Color32::from_rgb(
+        channel(0)?,
+        channel(2)?,
+        channel(4)?,
+    ))
+}
+
+// A color, or none to turn the element off
+fn parse_optional_color(s: Option<String>) -> Result<Option<egui::Color32>, ArgParseError> {
+    match s.as_deref() {
+        Some("none") => Ok(None),
+        Some(v) => parse_color(v).map(Some).ok_or(ArgParseError::InvalidColor),
+        None => Err(ArgParseError::InvalidColor),
+    }
+}
+
+fn generate_session_code() -> String {
+    let mut hasher = DefaultHasher::new();
+    SystemTime::now().hash(&mut hasher);
+    std::process::id().hash(&mut hasher);
+    format!("{:06x}", hasher.finish() & 0xff_ffff)
+}
+
+// How long batches from different devices wait for each other, so presses on the two halves of a
+// split keyboard are decoded in the order they happened
+const MERGE_WINDOW: Duration = Duration::from_millis(5);
+
+// Everything the kernel reported between two SYN_REPORTs. Keys in the same report went down
+// together, so they're handed to the app in one go
+type EventBatch = Vec<InputEvent>;
+
+struct InputEvent {
+    event: input_bindings::input_event,
+}
+
+// Same clock as the kernel's event timestamps
+fn now_timestamp() -> Duration {
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default()
+}
+
+impl InputEvent {
+    // An event that didn't come from the device, stamped with the current time
+    fn synthetic(type_: u32, code: u16, value: i32) -> InputEvent {
+        let now = now_timestamp();
+        let event = input_bindings::input_event {
+            time: input_bindings::timeval {
+                tv_sec: now.as_secs() as _,
+                tv_usec: now.subsec_micros() as _,
+            },
+            type_: type_ as u16,
+            code,
+            value,
+        };
+        InputEvent { event }
+    }
+
+    fn timestamp(&self) -> Duration {
+        Duration::new(
+            self.event.time.tv_sec as u64,
+            self.event.time.tv_usec as u32 * 1000,
+        )
+    }
+
+    // Events were lost and held key state can't be trusted anymore
+    fn is_resync(&self) -> bool {
+        self.event.type_ == input_bindings::EV_SYN as u16
+            && self.event.code == input_bindings::SYN_DROPPED as u16
+    }
+
+    fn is_syn_report(&self) -> bool {
+        self.event.type_ == input_bindings::EV_SYN as u16
+            && self.event.code == input_bindings::SYN_REPORT as u16
+    }
+}
+
+// Groups recorded events per SYN_REPORT and keeps the ones the reader would, so replays and frame
+// exports show the same as the live overlay
+#[derive(Default)]
+struct RecordingBatcher {
+    batch: EventBatch,
+}
+
+impl RecordingBatcher {
+    // Returns the batch once the event ends a report
+    fn push(&mut self, event: input_bindings::input_event) -> Option<EventBatch> {
+        let event = InputEvent { event };
+        if event.is_syn_report() {
+            return Some(std::mem::take(&mut self.batch)).filter(|b| !b.is_empty());
+        }
+
+        if event.event.type_ == input_bindings::EV_KEY as u16
+            || MouseInput::from_scroll(&event.event).is_some()
+        {
+            self.batch.push(event);
+        }
+        None
+    }
+
+    // Recordings cut off mid report still get their last events shown
+    fn finish(self) -> Option<EventBatch> {
+        Some(self.batch).filter(|b| !b.is_empty())
+    }
+}
+
+// A resync followed by a key down for everything the device says is held. Without that, keys
+// pressed while events were being lost would never show as held, and ones released would stay
+// held until pressed again
+fn resync_batch(f: &File, held_codes: &mut BTreeSet<u16>) -> EventBatch {
+    let mut batch = vec![InputEvent::synthetic(
+        input_bindings::EV_SYN,
+        input_bindings::SYN_DROPPED as u16,
+        0,
+    )];
+
+    let key_state = evdev::key_state(f).unwrap_or_default();
+    *held_codes = (0..=input_bindings::KEY_MAX)
+        .filter(|code| evdev::test_bit(&key_state, *code))
+        .map(|code| code as u16)
+        .collect();
+
+    batch.extend(
+        held_codes
+            .iter()
+            .map(|code| InputEvent::synthetic(input_bindings::EV_KEY, *code, 1)),
+    );
+    batch
+}
+
+// How long a device can be away before we tell the user it's gone
+const RECONNECT_GRACE: Duration = Duration::from_secs(5);
+
+fn reader_thread(
+    tx: Sender<EventBatch>,
+    toast_tx: Sender<Toast>,
+    rx: Receiver<egui::Context>,
+    mut f: File,
+    mut recorder: Option<EvemuRecorder>,
+    health: SharedHealth,
+) {
+    let ctx = rx.recv().unwrap();
+
+    let identity = hotplug::DeviceIdentity::of(&f);
+    let mut held_codes = BTreeSet::new();
+    let mut batch = Vec::new();
+
+    // Modifiers held while we start up would otherwise be missed until they're pressed again
+    let startup_state = resync_batch(&f, &mut held_codes);
+    if !held_codes.is_empty() {
+        let _ = tx.send(startup_state);
+        ctx.request_repaint();
+    }
+
+    unsafe {
+        loop {
+            let mut event = MaybeUninit::<input_bindings::input_event>::uninit();
+            {
+                let event_buf = std::slice::from_raw_parts_mut(
+                    event.as_mut_ptr() as *mut u8,
+                    core::mem::size_of::<input_bindings::input_event>(),
+                );
+                if let Err(e) = f.read_exact(event_buf) {
+                    health.lock().unwrap().record_error();
+
+                    // We won't see the releases for anything held when the device went away, or
+                    // the SYN_REPORT for a half read batch
+                    batch.clear();
+                    let releases = std::mem::take(&mut held_codes)
+                        .into_iter()
+                        .map(|code| InputEvent::synthetic(input_bindings::EV_KEY, code, 0))
+                        .collect::<Vec<_>>();
+                    if !releases.is_empty() {
+                        let _ = tx.send(releases);
+                        ctx.request_repaint();
+                    }
+
+                    let lost = || {
+                        let _ = toast_tx.send(Toast::error(format!("Lost input device: {e}")));
+                        ctx.request_repaint();
+                    };
+
+                    let identity = match &identity {
+                        Some(v) => v,
+                        None => return lost(),
+                    };
+
+                    // Bluetooth keyboards drop out and come back within seconds, only say so if
+                    // the device stays away
+                    f = match hotplug::wait_for_device(identity, Some(RECONNECT_GRACE)) {
+                        Ok(Some(v)) => v,
+                        Ok(None) => {
+                            lost();
+                            match hotplug::wait_for_device(identity, None) {
+                                Ok(Some(v)) => {
+                                    let _ = toast_tx.send(Toast::info(format!(
+                                        "Reconnected {}",
+                                        identity.name()
+                                    )));
+                                    v
+                                }
+                                _ => return,
+                            }
+                        }
+                        Err(_) => return lost(),
+                    };
+
+                    // KVM switches re-enumerate the keyboard on every switch, and locks or
+                    // latches toggled on the other machine leave our keymap state stale
+                    let _ = tx.send(resync_batch(&f, &mut held_codes));
+                    ctx.request_repaint();
+                    continue;
+                }
+            }
+
+            let event = event.assume_init();
+            health.lock().unwrap().record_event();
+
+            // Recordings need every event, not just the ones we display
+            if let Some(r) = &mut recorder {
+                if let Err(e) = r.record(&event) {
+                    let _ = toast_tx.send(Toast::error(format!("Evemu recording stopped: {e:?}")));
+                    recorder = None;
+                }
+            }
+
+            let event = InputEvent { event };
+
+            // The kernel's buffer overflowed, whatever we think is held may have been released.
+            // The events until the next SYN_REPORT are part of the overflowed report, drop them
+            // with the rest of the batch and ask the device what's held instead
+            if event.is_resync() {
+                batch.clear();
+                tx.send(resync_batch(&f, &mut held_codes)).unwrap();
+                ctx.request_repaint();
+                continue;
+            }
+
+            if event.is_syn_report() {
+                if !batch.is_empty() {
+                    tx.send(std::mem::take(&mut batch)).unwrap();
+                    ctx.request_repaint();
+                }
+                continue;
+            }
+
+            // Wheel movement has no state to track, pointer motion is dropped here since it's
+            // most of what a mouse sends
+            if MouseInput::from_scroll(&event.event).is_some() {
+                batch.push(event);
+                continue;
+            }
+
+            // FIXME: Ioctl to filter on read
+            if event.event.type_ != input_bindings::EV_KEY as u16 {
+                continue;
+            }
+
+            match event.event.value {
+                0 => held_codes.remove(&event.event.code),
+                _ => held_codes.insert(event.event.code),
+            };
+
+            batch.push(event);
+        }
+    }
+}
+
+enum ReplayCommand {
+    SetSpeed(f32),
+    Seek(Chord),
+    // The app's keymap changed, seeking has to decode with the same one
+    SetKeymap(KeymapSource),
+}
+
+// Picks up devices plugged in after startup. The ones we already read from are reconnected by
+// their own reader
+fn discovery_thread(
+    tx: Sender<EventBatch>,
+    toast_tx: Sender<Toast>,
+    rx: Receiver<egui::Context>,
+    health_tx: Sender<SharedHealth>,
+    claimed: ClaimedDevices,
+    mice: bool,
+) {
+    let ctx = rx.recv().unwrap();
+
+    let matches: fn(&File) -> bool = match mice {
+        true => |f| discovery::is_keyboard(f) || discovery::is_mouse(f),
+        false => discovery::is_keyboard,
+    };
+
+    let ret = hotplug::watch_new_devices(&claimed, matches, |path, f| {
+        let name = evdev::device_name(&f).unwrap_or_else(|_| "Unknown device".to_string());
+        let _ = toast_tx.send(Toast::info(format!("Using {name} ({})", path.display())));
+
+        let health = DeviceHealth::new_shared(name, path);
+        let _ = health_tx.send(Arc::clone(&health));
+
+        let (context_tx, context_rx) = mpsc::channel();
+        let _ = context_tx.send(ctx.clone());
+        let tx = tx.clone();
+        let toast_tx = toast_tx.clone();
+        thread::spawn(move || reader_thread(tx, toast_tx, context_rx, f, None, health));
+        ctx.request_repaint();
+    });
+
+    if let Err(e) = ret {
+        let _ = toast_tx.send(Toast::error(format!(
+            "Stopped watching for new devices: {e}"
+        )));
+        ctx.request_repaint();
+    }
+}
+
+fn replay_thread(
+    tx: Sender<EventBatch>,
+    toast_tx: Sender<Toast>,
+    rx: Receiver<egui::Context>,
+    control_rx: Receiver<ReplayCommand>,
+    events: Vec<input_bindings::input_event>,
+    keymap_source: Option<KeymapSource>,
+) {
+    let ctx = rx.recv().unwrap();
+
+    // Seeking needs to decode keys without waiting on the gui for each event, so keep our own
+    // keymap state in step with the app's. The app sends us every keymap it switches to
+    let mut xkb = match &keymap_source {
+        Some(source) => Xkb::from_source(source),
+        None => Xkb::new(),
+    }
+    .expect("Failed to create xkb");
+    let mut modifiers = Modifiers::default();
+
+    let mut speed = 1.0;
+    let mut seek_chord = None;
+    let mut last_offset = Duration::ZERO;
+    let mut next_event_time = Instant::now();
+    let mut batcher = RecordingBatcher::default();
+    for mut event in events {
+        while let Ok(command) = control_rx.try_recv() {
+            match command {
+                ReplayCommand::SetSpeed(v) => speed = v,
+                ReplayCommand::Seek(chord) => seek_chord = Some(chord),
+                ReplayCommand::SetKeymap(source) => {
+                    // Already loaded by the app, so this only fails if something changed since
+                    if let Ok(v) = Xkb::from_source(&source) {
+                        xkb = v;
+                        modifiers = Modifiers::default();
+                    }
+                }
+            }
+        }
+
+        let offset = Duration::new(event.time.tv_sec as u64, event.time.tv_usec as u32 * 1000);
+        let delay = offset.saturating_sub(last_offset).div_f32(speed);
+        last_offset = offset;
+
+        if seek_chord.is_some() {
+            next_event_time = Instant::now();
+        } else {
+            next_event_time += delay;
+            thread::sleep(next_event_time.saturating_duration_since(Instant::now()));
+        }
+
+        // Hold and silence timers compare against the current time, so pretend the event just
+        // happened
+        let now = now_timestamp();
+        event.time.tv_sec = now.as_secs() as _;
+        event.time.tv_usec = now.subsec_micros() as _;
+
+        let input_event = InputEvent { event };
+        if let Some(press_state) = event_press_state(&input_event) {
+            if let Some(keypress) = xkb.push_keycode(event.code, &press_state) {
+                modifiers.update(&keypress, &press_state);
+
+                if let (KeyPress::Other(key_s), true) = (&keypress, is_keydown(&press_state)) {
+                    let item = KeyHistoryItem {
+                        key_s: key_s.clone(),
+                        modifiers: modifiers.clone(),
+                        action: None,
+                        annotation: None,
+                        pasted_chars: None,
+                        held_for: None,
+                        repeated: false,
+                        expanded: None,
+                        pressed_at: Instant::now(),
+                        app: None,
+                    };
+                    if seek_chord.as_ref().is_some_and(|c| c.matches(&item)) {
+                        seek_chord = None;
+                        let _ = toast_tx.send(Toast::info("Found chord, resuming replay"));
+                    }
+                }
+            }
+        }
+
+        if let Some(batch) = batcher.push(event) {
+            if tx.send(batch).is_err() {
+                return;
+            }
+            ctx.request_repaint();
+        }
+    }
+
+    if let Some(batch) = batcher.finish() {
+        let _ = tx.send(batch);
+    }
+
+    let _ = toast_tx.send(Toast::info("Replay finished"));
+    ctx.request_repaint();
+}
+
+// Loads the config and options the same way a normal start does, without opening any device
+fn check_config() -> ! {
+    let mut cli_args = std::env::args().collect::<Vec<_>>();
+    cli_args.remove(1);
+
+    let checked = match config::path(&cli_args) {
+        Some(v) => v.display().to_string(),
+        None => "command line options".to_string(),
+    };
+
+    let merged_args = match config::merge_args(cli_args) {
+        Ok(v) => v,
+        // Display points at the line and column
+        Err(config::ConfigError::Parse(e)) => {
+            eprintln!("{checked}: {e}");
+            std::process::exit(1);
+        }
+        Err(e) => {
+            eprintln!("{checked}: {e:?}");
+            std::process::exit(1);
+        }
+    };
+
+    let args = match Args::try_parse(merged_args.into_iter()) {
+        Ok(v) => v,
+        Err(e) => {
+            eprintln!("{checked}: {e:?}");
+            std::process::exit(1);
+        }
+    };
+
+    if let Some(source) = &args.keymap_source {
+        if let Err(e) = Xkb::from_source(source) {
+            eprintln!("{checked}: Failed to compile keymap: {e:?}");
+            std::process::exit(1);
+        }
+    }
+
+    println!("{checked}: OK");
+    std::process::exit(0);
+}
+
+fn main() {
+    if std::env::args().nth(1).as_deref() == Some("check-config") {
+        check_config();
+    }
+
+    let cli_args = config::merge_args(std::env::args().collect()).expect("Failed to load config");
+    let mut args = Args::parse(cli_args.into_iter());
+    let (toast_tx, toast_rx) = mpsc::channel();
+
+    // Recordings are of a single device
+    let discover_new_devices =
+        matches!(args.input_source, InputSource::Discover) && args.record_evemu.is_none();
+    if let InputSource::Discover = args.input_source {
+        let mut keyboards = discovery::find_keyboards();
+        if keyboards.is_empty() {
+            eprintln!("No readable keyboard in /dev/input, pass --event-input-path");
+            std::process::exit(1);
+        }
+
+        if args.mouse || args.click_ripple {
+            keyboards.extend(discovery::find_mice());
+        }
+
+        if args.record_evemu.is_some() {
+            keyboards.truncate(1);
+        }
+
+        let mut paths = Vec::new();
+        for keyboard in keyboards {
+            let _ = toast_tx.send(Toast::info(format!(
+                "Using {} ({})",
+                keyboard.name,
+                keyboard.path.display()
+            )));
+            paths.push(keyboard.path);
+        }
+        args.input_source = InputSource::Devices(paths);
+    }
+
+    let xkb = match &args.keymap_source {
+        Some(source) => Xkb::from_source(source),
+        // Replaced as soon as the compositor sends its keymap
+        None if args.wayland_keymap => Xkb::from_source(&KeymapSource::Names(RuleNames::default())),
+        None => Xkb::new(),
+    }
+    .expect("Failed to create xkb");
+
+    let (keycode_tx, keycode_rx) = mpsc::channel();
+    let mut context_txs = Vec::new();
+    let mut replay_tx = None;
+    let mut frame_exporter = None;
+    let mut device_health = Vec::new();
+    let mut new_device_rx = None;
+    match &args.input_source {
+        InputSource::Devices(paths) => {
+            let claimed_devices = ClaimedDevices::default();
+            for path in paths {
+                let f = File::open(path).expect("Failed to open event input");
+                claimed_devices
+                    .lock()
+                    .unwrap()
+                    .extend(hotplug::DeviceIdentity::of(&f));
+                // Multiple devices are refused when recording
+                let recorder = args.record_evemu.as_ref().map(|path| {
+                    let recorder = EvemuRecorder::create(path, &f, args.session_code.as_deref())
+                        .expect("Failed to start evemu recording");
+                    let _ = toast_tx.send(Toast::info(format!("Recording to {}", path.display())));
+                    recorder
+                });
+
+                let name = evdev::device_name(&f).unwrap_or_else(|_| "Unknown device".to_string());
+                let health = DeviceHealth::new_shared(name, path.clone());
+                device_health.push(Arc::clone(&health));
+
+                let keycode_tx = keycode_tx.clone();
+                let toast_tx = toast_tx.clone();
+                let (context_tx, context_rx) = mpsc::channel();
+                context_txs.push(context_tx);
+                thread::spawn(move || {
+                    reader_thread(keycode_tx, toast_tx, context_rx, f, recorder, health)
+                });
+            }
+
+            if discover_new_devices {
+                let (health_tx, health_rx) = mpsc::channel();
+                new_device_rx = Some(health_rx);
+                let toast_tx = toast_tx.clone();
+                let (context_tx, context_rx) = mpsc::channel();
+                context_txs.push(context_tx);
+                let mice = args.mouse || args.click_ripple;
+                thread::spawn(move || {
+                    discovery_thread(
+                        keycode_tx,
+                        toast_tx,
+                        context_rx,
+                        health_tx,
+                        claimed_devices,
+                        mice,
+                    )
+                });
+            }
+        }
+        InputSource::Replay(path) if args.export_frames.is_some() => {
+            let events = evemu::load_events(path).expect("Failed to load replay");
+            let dir = args.export_frames.clone().unwrap();
+            let exporter =
+                FrameExporter::new(dir, args.frame_rate, events, args.session_code.clone())
+                    .expect("Failed to start frame export");
+            frame_exporter = Some(exporter);
+        }
+        InputSource::Replay(path) => {
+            let events = evemu::load_events(path).expect("Failed to load replay");
+            let toast_tx = toast_tx.clone();
+            let (control_tx, control_rx) = mpsc::channel();
+            replay_tx = Some(control_tx);
+            let keymap_source = args.keymap_source.clone();
+            let (context_tx, context_rx) = mpsc::channel();
+            context_txs.push(context_tx);
+            thread::spawn(move || {
+                replay_thread(
+                    keycode_tx,
+                    toast_tx,
+                    context_rx,
+                    control_rx,
+                    events,
+                    keymap_source,
+                )
+            });
+        }
+        InputSource::X11 => {
+            let toast_tx = toast_tx.clone();
+            let (context_tx, context_rx) = mpsc::channel();
+            context_txs.push(context_tx);
+            x11_record::spawn_recorder(keycode_tx, toast_tx, context_rx)
+                .expect("Failed to start XRecord");
+        }
+        InputSource::Discover => unreachable!(),
+    }
+
+    let (command_tx, command_rx) = mpsc::channel();
+    let control_socket = args.control_socket.clone();
+    if let Some(path) = &control_socket {
+        let (context_tx, context_rx) = mpsc::channel();
+        ipc::spawn_listener(path, command_tx.clone(), context_rx)
+            .expect("Failed to create control socket");
+        context_txs.push(context_tx);
+    }
+
+    if args.wayland_keymap {
+        let (context_tx, context_rx) = mpsc::channel();
+        wayland_keymap::spawn_listener(command_tx.clone(), toast_tx.clone(), context_rx)
+            .expect("Failed to connect to wayland display");
+        context_txs.push(context_tx);
+    }
+
+    if let Some(KeymapSource::File(path)) = &args.keymap_source {
+        let (context_tx, context_rx) = mpsc::channel();
+        keymap_watch::spawn_watcher(path, command_tx, context_rx)
+            .expect("Failed to watch xkb mapping");
+        context_txs.push(context_tx);
+    }
+
+    let speech_tx = args
+        .speech_filter
+        .map(|_| speech::spawn_speaker(args.speech_interval, toast_tx.clone()));
+
+    let mqtt_tx = args
+        .mqtt_broker
+        .clone()
+        .map(|broker| mqtt::spawn_publisher(broker, args.mqtt_topic.clone(), toast_tx.clone()));
+
+    let clipboard = args.paste_length.map(|_| {
+        let (context_tx, context_rx) = mpsc::channel();
+        context_txs.push(context_tx);
+        clipboard::spawn_reader(context_rx).expect("Failed to read clipboard")
+    });
+
+    let focus_rx = args.app_sections.then(|| {
+        let (context_tx, context_rx) = mpsc::channel();
+        context_txs.push(context_tx);
+        focus::spawn_tracker(context_rx).expect("Failed to track focused window")
+    });
+
+    // A saved monitor that isn't connected right now shouldn't stop the overlay from starting,
+    // it's used once it shows up
+    if let Some(selector) = &args.monitor {
+        match monitors::find(selector) {
+            Ok(monitor) => {
+                let _ = toast_tx.send(Toast::info(format!("Showing on {}", monitor.name)));
+                args.monitor_rect = Some(monitor.rect);
+            }
+            Err(e) => {
+                let _ = toast_tx.send(Toast::error(format!(
+                    "Monitor {selector} not available, using the current one: {e:?}"
+                )));
+            }
+        }
+    }
+
+    let monitor_rx = args.monitor.clone().map(|selector| {
+        let (context_tx, context_rx) = mpsc::channel();
+        context_txs.push(context_tx);
+        monitors::spawn_watcher(selector, context_rx).expect("Failed to watch monitors")
+    });
+
+    // Transparent windows turn into black boxes without a compositor, errors leave transparency
+    // on since we can't tell
+    if !args.opaque && matches!(compositor::is_running(), Ok(false)) {
+        let _ = toast_tx.send(Toast::error(
+            "No compositor running, using an opaque background",
+        ));
+        args.opaque = true;
+    }
+
+    let mut native_options = eframe::NativeOptions::default();
+    if let Some(rect) = args.monitor_rect {
+        // Start out on the right monitor, the anchor takes care of the exact position
+        native_options.viewport = native_options.viewport.with_position(rect.min);
+    }
+    native_options.viewport = native_options
+        .viewport
+        .with_transparent(!args.opaque)
+        .with_decorations(false)
+        .with_always_on_top()
+        .with_mouse_passthrough(!args.interactive);
+
+    eframe::run_native(
+        "keyboard overlay",
+        native_options,
+        Box::new(move |cc| {
+            let channels = AppChannels {
+                rx: keycode_rx,
+                command_rx,
+                toast_rx,
+                context_txs,
+                replay_tx,
+                speech_tx,
+                mqtt_tx,
+                focus_rx,
+                monitor_rx,
+                clipboard,
+                device_health,
+                new_device_rx,
+            };
+            Box::new(App::new(cc, channels, xkb, frame_exporter, args))
+        }),
+    )
+    .expect("Failed to run gui");
+
+    if let Some(path) = &control_socket {
+        ipc::remove_stale_socket(path);
+    }
+}
+
+// Last keypress (plus modifier state)
+// Number of times pressed
+// When it was pressed
+
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+struct Modifiers {
+    ctrl: bool,
+    shift: bool,
+    alt: bool,
+    sup: bool,
+}
+
+impl Modifiers {
+    fn update(&mut self, key_press: &KeyPress, press_state: &KeyPressState) {
+        match key_press {
+            KeyPress::Alt => {
+                self.alt = is_keydown(press_state);
+            }
+            KeyPress::Ctrl => {
+                self.ctrl = is_keydown(press_state);
+            }
+            KeyPress::Shift => {
+                self.shift = is_keydown(press_state);
+            }
+            KeyPress::Super => {
+                self.sup = is_keydown(press_state);
+            }
+            _ => (),
+        };
+    }
+
+    fn union(&self, other: &Modifiers) -> Modifiers {
+        Modifiers {
+            ctrl: self.ctrl || other.ctrl,
+            shift: self.shift || other.shift,
+            alt: self.alt || other.alt,
+            sup: self.sup || other.sup,
+        }
+    }
+}
+
+pub struct HeldKey {
+    keypress: KeyPress,
+    // Kernel timestamp of the key down
+    pressed_at: Duration,
+}
+
+impl HeldKey {
+    fn held_for(&self, now: Duration) -> Duration {
+        now.saturating_sub(self.pressed_at)
+    }
+}
+
+struct KeyHistoryItem {
+    key_s: String,
+    modifiers: Modifiers,
+    action: Option<SpecialAction>,
+    // User provided name for the chord, from --annotate
+    annotation: Option<String>,
+    // Length of the text pasted by a paste chord, with --paste-length
+    pasted_chars: Option<usize>,
+    // Set on release, with --hold-duration
+    held_for: Option<Duration>,
+    // Autorepeated while held, with --repeat indicate
+    repeated: bool,
+    // Number of keys typed by a text expander, with --collapse-expansions
+    expanded: Option<usize>,
+    pressed_at: Instant,
+    // Focused application, with --app-sections
+    app: Option<String>,
+}
+
+struct RenderedItem {
+    text: String,
+    // The chord split up for --keycaps, and the rest of the text after it
+    keys: Vec<String>,
+    details: String,
+    count: usize,
+    action: Option<SpecialAction>,
+    // Newest press of the collapsed items
+    last_pressed: Instant,
+    app: Option<String>,
+}
+
+const MAX_LINES: usize = 40;
+// How long items take to fade out at the end of --fade-after
+const FADE_TIME: Duration = Duration::from_secs(1);
+
+struct AppChannels {
+    rx: Receiver<EventBatch>,
+    command_rx: Receiver<IpcCommand>,
+    toast_rx: Receiver<Toast>,
+    context_txs: Vec<Sender<egui::Context>>,
+    // Only set when replaying a recording
+    replay_tx: Option<Sender<ReplayCommand>>,
+    speech_tx: Option<Sender<String>>,
+    mqtt_tx: Option<Sender<String>>,
+    focus_rx: Option<Receiver<String>>,
+    monitor_rx: Option<Receiver<Option<monitors::Monitor>>>,
+    clipboard: Option<PasteChannels>,
+    // Stats shared with the device reader threads
+    device_health: Vec<SharedHealth>,
+    // Devices plugged in after startup
+    new_device_rx: Option<Receiver<SharedHealth>>,
+}
+
+struct App {
+    rx: Receiver<EventBatch>,
+    // Waiting to be put in order with other devices' batches
+    pending_batches: Vec<EventBatch>,
+    command_rx: Receiver<IpcCommand>,
+    toast_rx: Receiver<Toast>,
+    toasts: Toasts,
+    replay_tx: Option<Sender<ReplayCommand>>,
+    speech: Option<(Sender<String>, KeyFilter)>,
+    mqtt: Option<(Sender<String>, KeyFilter)>,
+    frame_exporter: Option<FrameExporter>,
+    // The recording's time with --export-frames, the real one otherwise
+    clock: Clock,
+    focus_rx: Option<Receiver<String>>,
+    focused_app: Option<String>,
+    paste_length: Option<usize>,
+    clipboard: Option<PasteChannels>,
+    // Only with --interactive
+    search: Option<String>,
+    xkb: Xkb,
+    pressed_keycodes: VecDeque<KeyHistoryItem>,
+    rendered_keycodes: Vec<RenderedItem>,
+    current_modifier_state: Modifiers,
+    mode: Mode,
+    held_keys: BTreeMap<u16, HeldKey>,
+    max_held_keys: usize,
+    show_on_hold: Option<KeyPress>,
+    hold_key_down: bool,
+    modifier_timeout: Option<Duration>,
+    modifier_grace: Option<Duration>,
+    // Modifiers held just before the last modifier release and when it happened
+    released_modifiers: Option<(Modifiers, Duration)>,
+    chord_window: Option<Duration>,
+    expansion_gap: Option<Duration>,
+    chord_timings: Option<ChordTimings>,
+    // Evdev timestamp of the last key down, how many keys in a row were within expansion_gap of
+    // each other and when the first of them was pressed
+    burst: (Duration, usize, Instant),
+    // When the newest history item was pressed, as an evdev timestamp and as its pressed_at
+    last_key_down: Option<(Duration, Instant)>,
+    silence_indicator: Option<Duration>,
+    seen_input: bool,
+    last_event_time: Instant,
+    session_code: Option<String>,
+    special_chords: SpecialChords,
+    clipboard_counts: Option<BTreeMap<SpecialAction, usize>>,
+    capturing: bool,
+    hidden: bool,
+    no_persist: bool,
+    dpad: Option<KeyGrid>,
+    chords_only: bool,
+    repeat_mode: RepeatMode,
+    mouse: bool,
+    ripples: Option<Ripples>,
+    // Shortest time between repaints while something animates, zero without --max-fps
+    animation_frame: Duration,
+    held_keys_hud: bool,
+    keycaps: bool,
+    key_grid: Option<KeyGrid>,
+    heatmap_reset_chord: Option<Chord>,
+    health_chord: Option<Chord>,
+    segment_chord: Option<Chord>,
+    segments: Option<Segments>,
+    stats: Option<Stats>,
+    key_log: Option<KeyLog>,
+    show_health: bool,
+    device_health: Vec<SharedHealth>,
+    new_device_rx: Option<Receiver<SharedHealth>>,
+    hold_timers: bool,
+    hold_duration: Option<Duration>,
+    // History items still waiting for their key to be released, by evdev code
+    unreleased_items: BTreeMap<u16, Instant>,
+    bad_chords: Vec<Chord>,
+    annotations: Vec<(Chord, String)>,
+    reactions: Reactions,
+    bad_chord_count: usize,
+    bad_chord_nudge: Option<(String, Instant)>,
+    unknown_keys: u64,
+    // Codes already reported, each is only toasted once per session
+    reported_unknown_keys: BTreeSet<u16>,
+    font_size: f32,
+    max_lines: usize,
+    columns: usize,
+    horizontal: bool,
+    symbols: Symbols,
+    anchor: Option<Anchor>,
+    monitor_rect: Option<egui::Rect>,
+    monitor_rx: Option<Receiver<Option<monitors::Monitor>>>,
+    margin: f32,
+    offset: egui::Vec2,
+    theme: Theme,
+    opaque: bool,
+    fade_after: Option<Duration>,
+    transition: Option<Duration>,
+}
+
+impl App {
+    fn new(
+        cc: &eframe::CreationContext<'_>,
+        channels: AppChannels,
+        xkb: Xkb,
+        frame_exporter: Option<FrameExporter>,
+        args: Args,
+    ) -> Self {
+        for tx in channels.context_txs {
+            tx.send(cc.egui_ctx.clone()).unwrap();
+        }
+
+        let session_code = args.session_code;
+
+        cc.egui_ctx
+            .style_mut(|style| style.visuals.window_fill = egui::Color32::TRANSPARENT);
+        if let Some(font) = args.font {
+            let mut fonts = egui::FontDefinitions::default();
+            fonts
+                .font_data
+                .insert("custom".to_string(), egui::FontData::from_owned(font));
+            // Keep the default fonts behind it for glyphs it doesn't have, e.g. emoji
+            for family in [FontFamily::Monospace, FontFamily::Proportional] {
+                fonts
+                    .families
+                    .entry(family)
+                    .or_default()
+                    .insert(0, "custom".to_string());
+            }
+            cc.egui_ctx.set_fonts(fonts);
+        }
+
+        let panel_fill = args.theme.panel_fill(args.opaque);
+        cc.egui_ctx
+            .style_mut(|style| style.visuals.panel_fill = panel_fill);
+
+        let clock = match frame_exporter {
+            Some(_) => Clock::recording(),
+            None => Clock::real(),
+        };
+        let started = clock.now();
+
+        let mut toasts = Toasts::new(args.toast_duration);
+        // Without the log the overlay still works, so carry on
+        let key_log = args.log_keys.and_then(|path| match KeyLog::open(&path) {
+            Ok(v) => {
+                toasts.push(Toast::info(format!(
+                    "Logging every key press to {}, including passwords",
+                    path.display()
+                )));
+                Some(v)
+            }
+            Err(e) => {
+                toasts.push(Toast::error(format!("Failed to open key log: {e:?}")));
+                None
+            }
+        });
+
+        App {
+            rx: channels.rx,
+            pending_batches: Vec::new(),
+            command_rx: channels.command_rx,
+            toast_rx: channels.toast_rx,
+            replay_tx: channels.replay_tx,
+            speech: channels.speech_tx.zip(args.speech_filter),
+            mqtt: channels.mqtt_tx.map(|tx| (tx, args.mqtt_filter)),
+            frame_exporter,
+            clock,
+            focus_rx: channels.focus_rx,
+            focused_app: None,
+            paste_length: args.paste_length,
+            clipboard: channels.clipboard,
+            search: args.interactive.then(String::new),
+            toasts,
+            pressed_keycodes: VecDeque::new(),
+            rendered_keycodes: Vec::new(),
+            current_modifier_state: Modifiers {
+                ctrl: false,
+                shift: false,
+                alt: false,
+                sup: false,
+            },
+            xkb,
+            mode: args.mode,
+            held_keys: BTreeMap::new(),
+            max_held_keys: 0,
+            show_on_hold: args.show_on_hold,
+            hold_key_down: false,
+            modifier_timeout: args.modifier_timeout,
+            modifier_grace: args.modifier_grace,
+            chord_window: args.chord_window,
+            expansion_gap: args.expansion_gap,
+            chord_timings: args.chord_timing.then(ChordTimings::default),
+            burst: (Duration::ZERO, 0, Instant::now()),
+            last_key_down: None,
+            released_modifiers: None,
+            silence_indicator: args.silence_indicator,
+            seen_input: false,
+            last_event_time: Instant::now(),
+            session_code: session_code.clone(),
+            special_chords: SpecialChords::new(args.special_chords),
+            clipboard_counts: args.clipboard_counter.then(BTreeMap::new),
+            capturing: !args.capture_on_demand,
+            hidden: args.start_hidden,
+            no_persist: args.no_persist,
+            dpad: args.dpad,
+            chords_only: args.chords_only,
+            repeat_mode: args.repeat_mode,
+            mouse: args.mouse,
+            ripples: args
+                .click_ripple
+                .then(|| Ripples::connect().expect("Failed to query the pointer position")),
+            animation_frame: args
+                .max_fps
+                .map(|fps| Duration::from_secs_f32(1.0 / fps))
+                .unwrap_or(Duration::ZERO),
+            held_keys_hud: args.held_keys_hud,
+            keycaps: args.keycaps,
+            key_grid: args.key_grid,
+            heatmap_reset_chord: args.heatmap_reset_chord,
+            health_chord: args.health_chord,
+            segments: (args.segment_chord.is_some() || args.segment_dir.is_some())
+                .then(|| Segments::new(args.segment_dir, session_code.clone(), started)),
+            stats: args
+                .stats_out
+                .map(|path| Stats::new(path, session_code.clone(), args.stats_aliases)),
+            key_log,
+            segment_chord: args.segment_chord,
+            show_health: false,
+            device_health: channels.device_health,
+            new_device_rx: channels.new_device_rx,
+            hold_timers: args.hold_timers,
+            hold_duration: args.hold_duration,
+            unreleased_items: BTreeMap::new(),
+            bad_chords: args.bad_chords,
+            annotations: args.annotations,
+            reactions: Reactions::new(args.reactions),
+            bad_chord_count: 0,
+            bad_chord_nudge: None,
+            unknown_keys: 0,
+            reported_unknown_keys: BTreeSet::new(),
+            font_size: args.font_size,
+            max_lines: args.max_lines,
+            columns: args.columns,
+            horizontal: args.horizontal,
+            symbols: args.symbols,
+            anchor: args.anchor,
+            monitor_rect: args.monitor_rect,
+            monitor_rx: channels.monitor_rx,
+            margin: args.margin,
+            offset: args.offset,
+            theme: args.theme,
+            opaque: args.opaque,
+            fade_after: args.fade_after,
+            transition: args.transition,
+        }
+    }
+
+    fn is_visible(&self) -> bool {
+        self.capturing && !self.hidden && (self.show_on_hold.is_none() || self.hold_key_down)
+    }
+
+    fn handle_command(&mut self, command: IpcCommand) {
+        match command {
+            IpcCommand::StartCapture => {
+                if self.capturing {
+                    return;
+                }
+
+                // Anything pressed or released while we weren't looking is unknown, start fresh
+                self.reset_key_state();
+                self.capturing = true;
+            }
+            IpcCommand::StopCapture => {
+                self.capturing = false;
+            }
+            IpcCommand::ReplaySpeed(speed) => {
+                self.send_replay_command(ReplayCommand::SetSpeed(speed))
+            }
+            IpcCommand::ReplaySeek(chord) => self.send_replay_command(ReplayCommand::Seek(chord)),
+            IpcCommand::Show => {
+                self.hidden = false;
+            }
+            IpcCommand::Hide => {
+                self.hidden = true;
+            }
+            IpcCommand::Segment(name) => self.start_segment(name),
+            IpcCommand::SetKeymap(source) => match Xkb::from_source(&source) {
+                Ok(xkb) => {
+                    // Held keys were decoded with the old keymap
+                    self.xkb = xkb;
+                    self.reset_key_state();
+                    if let Some(tx) = &self.replay_tx {
+                        let _ = tx.send(ReplayCommand::SetKeymap(source));
+                    }
+                    self.toasts.push(Toast::info("Keymap reloaded"));
+                }
+                Err(e) => self
+                    .toasts
+                    .push(Toast::error(format!("Failed to load keymap: {e:?}"))),
+            },
+        }
+    }
+
+    fn start_segment(&mut self, name: Option<String>) {
+        let now = self.clock.now();
+        let segments = self
+            .segments
+            .get_or_insert_with(|| Segments::new(None, None, now));
+        match segments.start(name, now) {
+            Ok(()) => self.toasts.push(Toast::info("New segment")),
+            Err(e) => self
+                .toasts
+                .push(Toast::error(format!("Failed to save segment: {e:?}"))),
+        }
+    }
+
+    fn step_frame_export(&mut self, ctx: &egui::Context) {
+        let exporter = match &mut self.frame_exporter {
+            Some(v) => v,
+            None => return,
+        };
+
+        match exporter.poll_screenshot(ctx) {
+            Ok(true) => (),
+            Ok(false) => return,
+            Err(e) => {
+                self.toasts
+                    .push(Toast::error(format!("Frame export failed: {e:?}")));
+                self.frame_exporter = None;
+                return;
+            }
+        }
+
+        if exporter.is_finished() {
+            ctx.send_viewport_cmd(egui::ViewportCommand::Close);
+            return;
+        }
+
+        let events = exporter.next_frame_events();
+        let frame_end = exporter.frame_end();
+        exporter.request_screenshot(ctx);
+
+        // Everything runs on the recording's time, so the frames come out the same however long
+        // each one takes to render. Events in a report share a timestamp, so reports don't span
+        // frames
+        let mut batcher = RecordingBatcher::default();
+        for mut event in events {
+            self.clock.set_offset(frame_export::event_offset(&event));
+            let now = self.clock.timestamp();
+            event.time.tv_sec = now.as_secs() as _;
+            event.time.tv_usec = now.subsec_micros() as _;
+
+            if let Some(batch) = batcher.push(event) {
+                self.process_input_batch(&batch);
+            }
+        }
+        if let Some(batch) = batcher.finish() {
+            self.process_input_batch(&batch);
+        }
+        self.clock.set_offset(frame_end);
+    }
+
+    fn send_replay_command(&mut self, command: ReplayCommand) {
+        let sent = self
+            .replay_tx
+            .as_ref()
+            .is_some_and(|tx| tx.send(command).is_ok());
+        if !sent {
+            self.toasts.push(Toast::error("Not replaying"));
+        }
+    }
+
+    fn reset_key_state(&mut self) {
+        self.xkb.reset_state().expect("Failed to reset xkb state");
+        self.held_keys.clear();
+        self.unreleased_items.clear();
+        self.current_modifier_state = Modifiers::default();
+        self.released_modifiers = None;
+        self.hold_key_down = false;
+    }
+
+    // Each device has its own reader, so batches from different devices can arrive out of order,
+    // e.g. Ctrl on one half of a split keyboard and c on the other. With more than one device,
+    // batches are held for MERGE_WINDOW and handed out in the order they happened
+    fn ready_batches(&mut self, ctx: &egui::Context) -> Vec<EventBatch> {
+        let batch_time = |batch: &EventBatch| batch.first().map(InputEvent::timestamp);
+
+        self.pending_batches.extend(self.rx.try_iter());
+        if self.device_health.len() < 2 {
+            return std::mem::take(&mut self.pending_batches);
+        }
+
+        self.pending_batches.sort_by_key(batch_time);
+        let cutoff = self.clock.timestamp().saturating_sub(MERGE_WINDOW);
+        let ready = self
+            .pending_batches
+            .partition_point(|batch| batch_time(batch).unwrap_or_default() <= cutoff);
+        let ret = self.pending_batches.drain(..ready).collect();
+
+        if let Some(oldest) = self.pending_batches.first().and_then(batch_time) {
+            ctx.request_repaint_after(
+                (oldest + MERGE_WINDOW).saturating_sub(self.clock.timestamp()),
+            );
+        }
+        ret
+    }
+
+    fn process_input_batch(&mut self, batch: &[InputEvent]) {
+        match batch.split_first() {
+            // The keys following a resync are the ones the device reports as held, they aren't
+            // new presses
+            Some((first, held)) if first.is_resync() => {
+                if !self.capturing {
+                    return;
+                }
+
+                self.reset_key_state();
+                for event in held {
+                    self.restore_held_key(event);
+                }
+                self.toasts.push(Toast::info("Input state resynchronized"));
+            }
+            _ => {
+                for event in batch {
+                    self.process_input_event(event);
+                }
+            }
+        }
+    }
+
+    fn restore_held_key(&mut self, event: &InputEvent) {
+        let keypress = match self
+            .xkb
+            .push_keycode(event.event.code, &KeyPressState::Down)
+        {
+            Some(v) => v,
+            None => return,
+        };
+
+        self.current_modifier_state
+            .update(&keypress, &KeyPressState::Down);
+        if self.show_on_hold.as_ref() == Some(&keypress) {
+            self.hold_key_down = true;
+        }
+
+        let held = HeldKey {
+            keypress,
+            pressed_at: event.timestamp(),
+        };
+        self.held_keys.insert(event.event.code, held);
+    }
+
+    fn process_input_event(&mut self, event: &InputEvent) {
+        if !self.capturing {
+            return;
+        }
+
+        // Scrolling has no release, so it goes to the history as soon as it happens
+        if let Some(scroll) = MouseInput::from_scroll(&event.event) {
+            if self.mouse {
+                self.last_event_time = self.clock.now();
+                self.seen_input = true;
+                let key_s = scroll.label().to_string();
+                let modifiers = self.chord_modifiers(event.timestamp());
+                self.record_key_down(&key_s, &modifiers, event.timestamp());
+                self.push_key_down(key_s, modifiers, event.timestamp());
+            }
+            return;
+        }
+
+        // Autorepeat, the key's press is already in the history
+        if event.event.value == KEY_REPEAT {
+            self.process_repeat(event.event.code);
+            return;
+        }
+
+        let press_state = match event_press_state(event) {
+            Some(v) => v,
+            None => return,
+        };
+
+        let mouse_button = MouseInput::from_button(event.event.code);
+        if let (Some(ripples), Some(_), true) =
+            (&mut self.ripples, mouse_button, is_keydown(&press_state))
+        {
+            ripples.click();
+        }
+
+        if mouse_button.is_some() && !self.mouse {
+            return;
+        }
+
+        self.last_event_time = self.clock.now();
+        self.seen_input = true;
+
+        let xkb_keypress = match mouse_button {
+            Some(button) => Some(KeyPress::Mouse(button)),
+            None => self.xkb.push_keycode(event.event.code, &press_state),
+        };
+
+        let keypress = match xkb_keypress {
+            Some(v) => v,
+            None => {
+                // Show something stable instead of dropping the key, so gaps in the mapping get
+                // noticed and reported
+                if is_keydown(&press_state) {
+                    if self.reported_unknown_keys.insert(event.event.code) {
+                        self.toasts.push(Toast::error(format!(
+                            "Unknown key with evdev code {}, please report it",
+                            event.event.code
+                        )));
+                    }
+                    self.unknown_keys += 1;
+                }
+                KeyPress::Other(format!("keycode:{}", event.event.code))
+            }
+        };
+
+        let held_for =
+            self.update_held_keys(event.event.code, &keypress, &press_state, event.timestamp());
+
+        if let (Some(stats), true) = (&mut self.stats, keypress.is_modifier()) {
+            if is_keydown(&press_state) {
+                stats.record_modifier(event.event.code);
+            }
+        }
+
+        let previous_modifiers = self.current_modifier_state.clone();
+        self.current_modifier_state.update(&keypress, &press_state);
+        if self.modifier_grace.is_some() && keypress.is_modifier() && !is_keydown(&press_state) {
+            self.released_modifiers = Some((previous_modifiers, event.timestamp()));
+        }
+
+        let key_s = match &keypress {
+            KeyPress::Other(s) => Some(s.clone()),
+            KeyPress::Mouse(m) => Some(m.label().to_string()),
+            _ => None,
+        };
+
+        // Counted whether or not the history ends up showing it
+        let modifiers = match &key_s {
+            Some(key_s) if is_keydown(&press_state) => {
+                let modifiers = self.chord_modifiers(event.timestamp());
+                self.record_key_down(key_s, &modifiers, event.timestamp());
+                self.record_hands(event.event.code);
+                modifiers
+            }
+            _ => Modifiers::default(),
+        };
+
+        if self.show_on_hold.as_ref() == Some(&keypress) {
+            self.hold_key_down = is_keydown(&press_state);
+            if !keypress.is_modifier() {
+                return;
+            }
+        }
+
+        let key_s = match key_s {
+            Some(v) => v,
+            None => return,
+        };
+
+        if !is_keydown(&press_state) {
+            if let Some(held_for) = held_for {
+                self.label_hold(event.event.code, held_for);
+            }
+            return;
+        }
+
+        if let Some(pressed_at) = self.push_key_down(key_s, modifiers, event.timestamp()) {
+            self.unreleased_items.insert(event.event.code, pressed_at);
+        }
+    }
+
+    fn process_repeat(&mut self, code: u16) {
+        if self.repeat_mode == RepeatMode::Ignore {
+            return;
+        }
+
+        let pressed_at = match self.unreleased_items.get(&code) {
+            Some(v) => *v,
+            None => return,
+        };
+
+        let item = match self
+            .pressed_keycodes
+            .iter_mut()
+            .rfind(|item| item.pressed_at == pressed_at)
+        {
+            Some(v) => v,
+            None => return,
+        };
+
+        match self.repeat_mode {
+            RepeatMode::Ignore => return,
+            // Consecutive presses of the same chord are collapsed, so another copy bumps the
+            // counter
+            RepeatMode::Count => {
+                let repeat = KeyHistoryItem {
+                    key_s: item.key_s.clone(),
+                    modifiers: item.modifiers.clone(),
+                    action: item.action,
+                    annotation: item.annotation.clone(),
+                    pasted_chars: None,
+                    held_for: None,
+                    repeated: false,
+                    expanded: None,
+                    pressed_at: self.clock.now(),
+                    app: item.app.clone(),
+                };
+                self.pressed_keycodes.push_back(repeat);
+            }
+            RepeatMode::Indicate => {
+                if item.repeated {
+                    return;
+                }
+                item.repeated = true;
+            }
+        }
+
+        self.update_rendered_keycodes();
+    }
+
+    fn label_hold(&mut self, code: u16, held_for: Duration) {
+        let pressed_at = match self.unreleased_items.remove(&code) {
+            Some(v) => v,
+            None => return,
+        };
+
+        match self.hold_duration {
+            Some(min) if held_for >= min => (),
+            _ => return,
+        }
+
+        let item = self
+            .pressed_keycodes
+            .iter_mut()
+            .rfind(|item| item.pressed_at == pressed_at);
+        if let Some(item) = item {
+            item.held_for = Some(held_for);
+            self.update_rendered_keycodes();
+        }
+    }
+
+    fn label_paste(&mut self, pasted_at: Instant, chars: usize) {
+        if self.paste_length.is_some_and(|min_chars| chars < min_chars) {
+            return;
+        }
+
+        // The paste may have scrolled out of the history already
+        let item = self
+            .pressed_keycodes
+            .iter_mut()
+            .rfind(|item| item.pressed_at == pasted_at);
+        if let Some(item) = item {
+            item.pasted_chars = Some(chars);
+            self.update_rendered_keycodes();
+        }
+    }
+
+    // Stats, timings and the key log for a key down of a non-modifier key, before anything decides
+    // whether the history shows it
+    // Which hands the held modifiers and the key are typed with, for the stats
+    fn record_hands(&mut self, code: u16) {
+        let stats = match &mut self.stats {
+            Some(v) => v,
+            None => return,
+        };
+
+        let modifier_codes = self
+            .held_keys
+            .iter()
+            .filter(|(_, k)| k.keypress.is_modifier())
+            .map(|(code, _)| *code)
+            .collect::<Vec<_>>();
+        stats.record_hands(code, &modifier_codes);
+    }
+
+    fn record_key_down(&mut self, key_s: &str, modifiers: &Modifiers, timestamp: Duration) {
+        if let Some(stats) = &mut self.stats {
+            stats.record(key_s, modifiers);
+        }
+
+        if let Some(log) = &mut self.key_log {
+            if let Err(e) = log.write(key_s, modifiers, timestamp) {
+                self.toasts
+                    .push(Toast::error(format!("Key log stopped: {e:?}")));
+                self.key_log = None;
+            }
+        }
+
+        if let Some(timings) = &mut self.chord_timings {
+            let first_modifier = self
+                .held_keys
+                .values()
+                .filter(|k| k.keypress.is_modifier())
+                .map(|k| k.pressed_at)
+                .min();
+            if let Some(first_modifier) = first_modifier {
+                let chord = Symbols::plain().chord(modifiers, key_s);
+                timings.record(chord, timestamp.saturating_sub(first_modifier));
+            }
+        }
+    }
+
+    // Adds a key down of a non-modifier key to the history. Returns when the added item was
+    // pressed, which identifies it later
+    fn push_key_down(
+        &mut self,
+        key_s: String,
+        modifiers: Modifiers,
+        timestamp: Duration,
+    ) -> Option<Instant> {
+        if let Some(pressed_at) = self.collapse_expansion(timestamp) {
+            return Some(pressed_at);
+        }
+
+        if self.chords_only && modifiers == Modifiers::default() {
+            return None;
+        }
+
+        if let Some(pressed_at) = self.group_key_down(&key_s, &modifiers, timestamp) {
+            return Some(pressed_at);
+        }
+
+        let mut key_press_event = KeyHistoryItem {
+            key_s,
+            modifiers,
+            action: None,
+            annotation: None,
+            pasted_chars: None,
+            held_for: None,
+            repeated: false,
+            expanded: None,
+            pressed_at: self.clock.now(),
+            app: self.focused_app.clone(),
+        };
+        key_press_event.action = self.special_chords.find(&key_press_event);
+        key_press_event.annotation = self
+            .annotations
+            .iter()
+            .find(|(chord, _)| chord.matches(&key_press_event))
+            .map(|(_, name)| name.clone());
+
+        // Reactions take the place of the chord
+        if self.reactions.trigger(&key_press_event, self.clock.now()) {
+            return None;
+        }
+
+        if let Some((tx, filter)) = &self.speech {
+            if filter.accepts(&key_press_event) {
+                let _ = tx.send(speech::spoken_text(&key_press_event));
+            }
+        }
+
+        if let Some((tx, filter)) = &self.mqtt {
+            if filter.accepts(&key_press_event) {
+                // Subscribers get the same text whether or not the overlay uses --symbols
+                let text = render_item(&key_press_event, &1, &Symbols::plain()).text;
+                let _ = tx.send(text.trim().to_string());
+            }
+        }
+
+        if self
+            .health_chord
+            .as_ref()
+            .is_some_and(|c| c.matches(&key_press_event))
+        {
+            self.show_health = !self.show_health;
+        }
+
+        // The marker chord belongs to neither segment
+        if self
+            .segment_chord
+            .as_ref()
+            .is_some_and(|c| c.matches(&key_press_event))
+        {
+            self.start_segment(None);
+        } else if let Some(segments) = &mut self.segments {
+            segments.record(&key_press_event);
+        }
+
+        if let (Some(chord), Some(grid)) = (&self.heatmap_reset_chord, &mut self.key_grid) {
+            if chord.matches(&key_press_event) {
+                grid.reset_heatmap();
+            }
+        }
+
+        if self.bad_chords.iter().any(|c| c.matches(&key_press_event)) {
+            self.bad_chord_count += 1;
+            let text = render_item(&key_press_event, &1, &self.symbols).text;
+            self.bad_chord_nudge = Some((text, self.clock.now()));
+        }
+
+        if let (Some((tx, _)), Some(SpecialAction::Paste)) =
+            (&self.clipboard, key_press_event.action)
+        {
+            let _ = tx.send(key_press_event.pressed_at);
+        }
+
+        if let (Some(counts), Some(action)) = (&mut self.clipboard_counts, key_press_event.action) {
+            if action.is_clipboard() {
+                *counts.entry(action).or_default() += 1;
+            }
+        }
+
+        let pressed_at = key_press_event.pressed_at;
+        self.pressed_keycodes.push_back(key_press_event);
+        self.update_rendered_keycodes();
+        self.last_key_down = Some((timestamp, pressed_at));
+        Some(pressed_at)
+    }
+
+    // Adds the key to the newest item if both were pressed within --chord-window of each other,
+    // e.g. "w + d". The window starts at the first key of the group
+    fn group_key_down(
+        &mut self,
+        key_s: &str,
+        modifiers: &Modifiers,
+        timestamp: Duration,
+    ) -> Option<Instant> {
+        let window = self.chord_window?;
+        let (last_timestamp, last_pressed_at) = self.last_key_down?;
+        if timestamp.saturating_sub(last_timestamp) > window {
+            return None;
+        }
+
+        let item = self
+            .pressed_keycodes
+            .back_mut()
+            .filter(|item| item.pressed_at == last_pressed_at && item.modifiers == *modifiers)?;
+        item.key_s = format!("{} + {}", item.key_s, key_s);
+        if let Some(timings) = &mut self.chord_timings {
+            let sequence = Symbols::plain().chord(&item.modifiers, &item.key_s);
+            timings.record(sequence, timestamp.saturating_sub(last_timestamp));
+        }
+        // Special chords and annotations were for the first key alone
+        item.action = None;
+        item.annotation = None;
+        self.update_rendered_keycodes();
+        Some(last_pressed_at)
+    }
+
+    // Text expanders type a whole snippet faster than anyone can. Once EXPANSION_MIN_KEYS keys in a
+    // row came within --collapse-expansions of each other they're replaced by one line, which
+    // takes the rest of the burst
+    fn collapse_expansion(&mut self, timestamp: Duration) -> Option<Instant> {
+        const EXPANSION_MIN_KEYS: usize = 4;
+
+        let gap = self.expansion_gap?;
+        let (last_timestamp, len, started) = self.burst;
+        if timestamp.saturating_sub(last_timestamp) > gap {
+            self.burst = (timestamp, 1, self.clock.now());
+            return None;
+        }
+        self.burst = (timestamp, len + 1, started);
+
+        if let Some(item) = self.pressed_keycodes.back_mut() {
+            if let Some(keys) = &mut item.expanded {
+                *keys += 1;
+                let pressed_at = item.pressed_at;
+                self.update_rendered_keycodes();
+                return Some(pressed_at);
+            }
+        }
+
+        if len + 1 < EXPANSION_MIN_KEYS {
+            return None;
+        }
+
+        // Only what the burst added, keys may have been left out of the history, e.g. with
+        // --chords-only
+        while self
+            .pressed_keycodes
+            .back()
+            .is_some_and(|item| item.pressed_at >= started)
+        {
+            self.pressed_keycodes.pop_back();
+        }
+
+        self.pressed_keycodes.push_back(KeyHistoryItem {
+            key_s: String::new(),
+            modifiers: Modifiers::default(),
+            action: None,
+            annotation: None,
+            pasted_chars: None,
+            held_for: None,
+            repeated: false,
+            expanded: Some(len + 1),
+            pressed_at: started,
+            app: self.focused_app.clone(),
+        });
+        self.update_rendered_keycodes();
+        Some(started)
+    }
+
+    fn update_rendered_keycodes(&mut self) {
+        let (mut rendered_keycodes, mut used_items) = render_keycodes(
+            self.pressed_keycodes.iter().rev(),
+            self.max_lines * self.columns,
+            &self.symbols,
+        );
+
+        if let Some(fade_after) = self.fade_after {
+            // Collapsed items expire together, once their newest press is old enough
+            while let Some(item) = rendered_keycodes.last() {
+                if self.clock.since(item.last_pressed) < fade_after {
+                    break;
+                }
+                used_items -= item.count;
+                rendered_keycodes.pop();
+            }
+        }
+
+        self.rendered_keycodes = rendered_keycodes;
+
+        let unused_items = self.pressed_keycodes.len() - used_items;
+        self.pressed_keycodes.drain(..unused_items);
+    }
+
+    // Returns how long until the oldest item starts fading
+    fn check_fade(&mut self) -> Option<Duration> {
+        let fade_after = self.fade_after?;
+        self.update_rendered_keycodes();
+
+        let oldest = self.rendered_keycodes.last()?;
+        let fade_start = fade_after.saturating_sub(FADE_TIME);
+        Some(fade_start.saturating_sub(self.clock.since(oldest.last_pressed)))
+    }
+
+    fn fade_alpha(&self, item: &RenderedItem) -> f32 {
+        let fade_after = match self.fade_after {
+            Some(v) => v,
+            None => return 1.0,
+        };
+
+        let fade_time = FADE_TIME.min(fade_after);
+        let remaining = fade_after.saturating_sub(self.clock.since(item.last_pressed));
+        (remaining.as_secs_f32() / fade_time.as_secs_f32()).min(1.0)
+    }
+
+    // Modifiers for a key pressed at timestamp, including ones released within the grace window.
+    // KVM switches in particular can deliver the modifier release before the key
+    fn chord_modifiers(&mut self, timestamp: Duration) -> Modifiers {
+        let current = self.current_modifier_state.clone();
+        let (grace, (released, released_at)) =
+            match (self.modifier_grace, self.released_modifiers.take()) {
+                (Some(grace), Some(released)) => (grace, released),
+                _ => return current,
+            };
+
+        if timestamp.saturating_sub(released_at) > grace {
+            return current;
+        }
+
+        current.union(&released)
+    }
+
+    fn update_held_keys(
+        &mut self,
+        code: u16,
+        keypress: &KeyPress,
+        press_state: &KeyPressState,
+        timestamp: Duration,
+    ) -> Option<Duration> {
+        if !is_keydown(press_state) {
+            let held = self.held_keys.remove(&code)?;
+            let held_for = timestamp.saturating_sub(held.pressed_at);
+            for grid in [&mut self.dpad, &mut self.key_grid].into_iter().flatten() {
+                grid.record_release(code, held_for);
+            }
+            return Some(held_for);
+        }
+
+        let held = HeldKey {
+            keypress: keypress.clone(),
+            pressed_at: timestamp,
+        };
+        self.held_keys.insert(code, held);
+        if let Some(grid) = &mut self.key_grid {
+            grid.record_press(code);
+        }
+        self.max_held_keys = self.max_held_keys.max(self.held_keys.len());
+        None
+    }
+
+    // Clears modifiers that look stuck, e.g. when their key up was swallowed by a screen locker.
+    // Returns how long until the watchdog needs to be checked again
+    fn check_modifier_watchdog(&mut self) -> Option<Duration> {
+        let timeout = self.modifier_timeout?;
+
+        if self.current_modifier_state == Modifiers::default()
+            || self.held_keys.values().any(|k| !k.keypress.is_modifier())
+        {
+            return None;
+        }
+
+        let elapsed = self.clock.since(self.last_event_time);
+        if elapsed < timeout {
+            return Some(timeout - elapsed);
+        }
+
+        self.current_modifier_state = Modifiers::default();
+        self.held_keys.retain(|_, k| !k.keypress.is_modifier());
+        None
+    }
+
+    // A device that was in use and then goes quiet has most likely been grabbed by something
+    // else (e.g. a VM with evdev passthrough). Returns how long it has been silent once that
+    // exceeds the configured threshold
+    fn check_silence(&self, ctx: &egui::Context) -> Option<Duration> {
+        let threshold = self.silence_indicator.filter(|_| self.seen_input)?;
+
+        let elapsed = self.clock.since(self.last_event_time);
+        if elapsed < threshold {
+            ctx.request_repaint_after(threshold - elapsed);
+            return None;
+        }
+
+        // Keep the displayed time ticking
+        ctx.request_repaint_after(Duration::from_secs(1));
+        Some(elapsed)
+    }
+
+    fn check_bad_chord_nudge(&mut self, ctx: &egui::Context) -> Option<String> {
+        const NUDGE_DURATION: Duration = Duration::from_secs(3);
+
+        let (chord_s, shown_at) = self.bad_chord_nudge.as_ref()?;
+        let elapsed = self.clock.since(*shown_at);
+        if elapsed >= NUDGE_DURATION {
+            self.bad_chord_nudge = None;
+            return None;
+        }
+
+        ctx.request_repaint_after(NUDGE_DURATION - elapsed);
+        Some(format!(
+            "⚠ {} is on your avoid list ({} this session)",
+            chord_s.trim(),
+            self.bad_chord_count
+        ))
+    }
+
+    fn held_keys_text(&self) -> String {
+        let now = self.clock.timestamp();
+        let held: Vec<String> = self
+            .held_keys
+            .values()
+            .map(|k| {
+                let name = self.symbols.get(k.keypress.display_name());
+                if self.hold_timers {
+                    format!("{name} {:.1}s", k.held_for(now).as_secs_f32())
+                } else {
+                    name.to_string()
+                }
+            })
+            .collect();
+        format!("Held ({}): {}", held.len(), held.join(" + "))
+    }
+
+    // Matches the key text or the application section, ignoring case
+    fn matches_search(&self, item: &RenderedItem) -> bool {
+        let search = match &self.search {
+            Some(v) if !v.is_empty() => v.to_lowercase(),
+            _ => return true,
+        };
+
+        item.text.to_lowercase().contains(&search)
+            || item
+                .app
+                .as_ref()
+                .is_some_and(|app| app.to_lowercase().contains(&search))
+    }
+
+    fn grows_down(&self) -> bool {
+        self.anchor.is_some_and(|a| a.grows_down())
+    }
+
+    fn history_layout(&self) -> egui::Layout {
+        match self.anchor {
+            Some(anchor) => anchor.layout(),
+            None => egui::Layout::bottom_up(egui::Align::default()),
+        }
+    }
+
+    // Moves the window back into its anchored corner, e.g. after the window manager placed it or
+    // it was resized. Wayland doesn't let clients position their windows so this does nothing there
+    fn position_window(&self, ctx: &egui::Context) {
+        let anchor = match self.anchor {
+            Some(v) => v,
+            None => return,
+        };
+
+        let (monitor_size, outer_rect, pixels_per_point) = ctx.input(|i| {
+            let viewport = i.viewport();
+            (
+                viewport.monitor_size,
+                viewport.outer_rect,
+                viewport.native_pixels_per_point.unwrap_or(1.0),
+            )
+        });
+        let (monitor_size, outer_rect) = match (monitor_size, outer_rect) {
+            (Some(m), Some(o)) => (m, o),
+            _ => return,
+        };
+
+        // Without --monitor, whichever monitor the window is on, assumed to be at the origin
+        let monitor = match self.monitor_rect {
+            Some(r) => egui::Rect::from_min_max(r.min / pixels_per_point, r.max / pixels_per_point),
+            None => egui::Rect::from_min_size(egui::Pos2::ZERO, monitor_size),
+        };
+        let position =
+            anchor.window_position(monitor, outer_rect.size(), self.margin) + self.offset;
+        if (outer_rect.min - position).length() > 0.5 {
+            ctx.send_viewport_cmd(egui::ViewportCommand::OuterPosition(position));
+        }
+    }
+
+    fn render_section_header(&self, ui: &mut egui::Ui, app: &Option<String>) {
+        if let Some(app) = app {
+            let label_text = RichText::new(format!("— {app} —"))
+                .family(FontFamily::Monospace)
+                .color(egui::Color32::GRAY)
+                .size(self.font_size);
+
+            ui.label(label_text);
+        }
+    }
+
+    fn render_history(&self, ui: &mut egui::Ui, items: &[RenderedItem]) {
+        let grows_down = self.grows_down();
+        let mut item_it = items
+            .iter()
+            .filter(|item| self.matches_search(item))
+            .peekable();
+        let mut newer_app = None;
+        while let Some(item) = item_it.next() {
+            // Headers go at the top of their section. Items are newest first, so that's before the
+            // newest item when growing down and after the oldest when growing up
+            if grows_down && newer_app != Some(&item.app) {
+                self.render_section_header(ui, &item.app);
+            }
+
+            let color = match item.action {
+                Some(_) => egui::Color32::from_rgb(255, 200, 80),
+                None => self.theme.text,
+            };
+            let alpha = self.fade_alpha(item);
+
+            let color = color.gamma_multiply(alpha);
+
+            if self.keycaps {
+                ui.horizontal(|ui| {
+                    for key in &item.keys {
+                        let font_id = egui::FontId::monospace(self.font_size);
+                        self.theme.keycap(ui, key, font_id, color, alpha);
+                    }
+
+                    let label_text = RichText::new(&item.details)
+                        .family(FontFamily::Monospace)
+                        .color(color)
+                        .size(self.font_size);
+                    ui.label(label_text);
+                });
+            } else {
+                let label_text = RichText::new(&item.text)
+                    .family(FontFamily::Monospace)
+                    .color(color)
+                    .size(self.font_size);
 
-        Ok(Args { event_input_path })
-    }
+                self.theme.label(ui, label_text, self.font_size, alpha);
+            }
 
-    fn parse<It: Iterator<Item = String>>(arg_it: It) -> Args {
-        match Self::try_parse(arg_it) {
-            Ok(v) => v,
-            Err(e) => {
-                println!("Argument parsing failed: {e:?}");
-                println!("{}", Args::help());
-                std::process::exit(1);
+            let older_app = item_it.peek().map(|older| &older.app);
+            if !grows_down && older_app != Some(&item.app) {
+                self.render_section_header(ui, &item.app);
             }
+            newer_app = Some(&item.app);
         }
     }
 
-    fn help() -> String {
-        "\n\
-            keyboard-overlay: Displays keys in an overlay\n\
-\n\
-            Args:\n\
-            --event-input-path [path]: Path to read keyboard events from\n\
-            --help: Show this help and exit\n\
-        "
-        .to_string()
+    fn render_kro_test(&self, ui: &mut egui::Ui) {
+        if let Some(grid) = &self.key_grid {
+            grid.render(ui, &self.held_keys, self.clock.timestamp(), &self.theme);
+        }
+
+        let text = format!(
+            "Held: {}  Max simultaneous keys: {}",
+            self.held_keys.len(),
+            self.max_held_keys
+        );
+        let label_text = RichText::new(text)
+            .family(FontFamily::Monospace)
+            .color(self.theme.text)
+            .size(self.font_size);
+
+        ui.label(label_text);
     }
 }
 
-struct InputEvent {
-    event: input_bindings::input_event,
-}
+impl eframe::App for App {
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        while let Ok(command) = self.command_rx.try_recv() {
+            self.handle_command(command);
+        }
 
-fn reader_thread(tx: Sender<InputEvent>, rx: Receiver<egui::Context>, event_input_path: PathBuf) {
-    let ctx = rx.recv().unwrap();
+        if let Some(rx) = &self.new_device_rx {
+            self.device_health.extend(rx.try_iter());
+        }
 
-    let mut f = File::open(event_input_path).unwrap();
+        if let Some(rx) = &self.focus_rx {
+            while let Ok(app) = rx.try_recv() {
+                self.focused_app = Some(app);
+            }
+        }
 
-    unsafe {
-        loop {
-            let mut event = MaybeUninit::<input_bindings::input_event>::uninit();
-            {
-                let event_buf = std::slice::from_raw_parts_mut(
-                    event.as_mut_ptr() as *mut u8,
-                    core::mem::size_of::<input_bindings::input_event>(),
-                );
-                f.read_exact(event_buf).unwrap();
+        if let Some(rx) = &self.monitor_rx {
+            while let Ok(monitor) = rx.try_recv() {
+                self.monitor_rect = monitor.map(|m| m.rect);
             }
+        }
 
-            let event = event.assume_init();
+        let pastes = match &self.clipboard {
+            Some((_, rx)) => rx.try_iter().collect(),
+            None => Vec::new(),
+        };
+        for (pasted_at, chars) in pastes {
+            self.label_paste(pasted_at, chars);
+        }
 
-            // FIXME: Ioctl to filter on read
-            // from input-event-codes.h
-            const EV_KEY: u16 = 1;
+        for batch in self.ready_batches(ctx) {
+            self.process_input_batch(&batch);
+        }
+        self.step_frame_export(ctx);
+        self.position_window(ctx);
+        if let Some(ripples) = &mut self.ripples {
+            ripples.render(ctx, self.theme.text, self.animation_frame);
+        }
 
-            if event.type_ != EV_KEY {
-                continue;
-            }
+        while let Ok(toast) = self.toast_rx.try_recv() {
+            self.toasts.push(toast);
+        }
+        self.toasts.expire(ctx, self.clock.now());
 
-            let event = InputEvent { event };
+        if let Some(stats) = &mut self.stats {
+            if stats.next_write().is_zero() {
+                if let Err(e) = stats.write(self.chord_timings.as_ref()) {
+                    self.toasts
+                        .push(Toast::error(format!("Failed to save stats: {e:?}")));
+                }
+            }
+            ctx.request_repaint_after(stats.next_write());
+        }
 
-            tx.send(event).unwrap();
-            ctx.request_repaint();
+        if let Some(recheck) = self.check_modifier_watchdog() {
+            ctx.request_repaint_after(recheck);
         }
-    }
-}
 
-fn main() {
-    let args = Args::parse(std::env::args());
+        // While items are fading we need every frame, otherwise only wake up when the next one
+        // starts to
+        match self.check_fade() {
+            Some(Duration::ZERO) => ctx.request_repaint_after(self.animation_frame),
+            Some(v) => ctx.request_repaint_after(v),
+            None => (),
+        }
 
-    let xkb = Xkb::new().expect("Failed to create xkb");
+        let silent_for = self.check_silence(ctx);
+        let nudge = self.check_bad_chord_nudge(ctx);
 
-    let (keycode_tx, keycode_rx) = mpsc::channel();
-    let (context_tx, context_rx) = mpsc::channel();
-    let _t = thread::spawn(move || reader_thread(keycode_tx, context_rx, args.event_input_path));
+        if self.hold_timers && !self.held_keys.is_empty() {
+            ctx.request_repaint_after(self.animation_frame);
+        }
 
-    let mut native_options = eframe::NativeOptions::default();
-    native_options.viewport = native_options
-        .viewport
-        .with_transparent(true)
-        .with_decorations(false)
-        .with_always_on_top()
-        .with_mouse_passthrough(true);
+        // 0 is fully hidden, 1 fully shown
+        let visibility = match self.transition {
+            Some(t) => ctx.animate_bool_with_time(
+                egui::Id::new("overlay_visibility"),
+                self.is_visible(),
+                t.as_secs_f32(),
+            ),
+            None if self.is_visible() => 1.0,
+            None => 0.0,
+        };
 
-    eframe::run_native(
-        "keyboard overlay",
-        native_options,
-        Box::new(move |cc| Box::new(App::new(cc, keycode_rx, context_tx, xkb))),
-    )
-    .expect("Failed to run gui");
-}
+        if visibility == 0.0 {
+            return;
+        }
 
-// Last keypress (plus modifier state)
-// Number of times pressed
-// When it was pressed
+        self.toasts.render(ctx);
+        self.reactions
+            .render(ctx, self.animation_frame, self.clock.now());
 
-#[derive(Clone, Eq, PartialEq)]
-struct Modifiers {
-    ctrl: bool,
-    shift: bool,
-    alt: bool,
-    sup: bool,
-}
+        if self.show_health {
+            health::render(ctx, &self.device_health);
+            // Keep event ages ticking
+            ctx.request_repaint_after(Duration::from_millis(500));
+        }
 
-impl Modifiers {
-    fn update(&mut self, key_press: &KeyPress, press_state: &KeyPressState) {
-        match key_press {
-            KeyPress::Alt => {
-                self.alt = is_keydown(press_state);
-            }
-            KeyPress::Ctrl => {
-                self.ctrl = is_keydown(press_state);
-            }
-            KeyPress::Shift => {
-                self.shift = is_keydown(press_state);
-            }
-            KeyPress::Super => {
-                self.sup = is_keydown(press_state);
+        egui::CentralPanel::default().show(ctx, |ui| {
+            if let Some(code) = &self.session_code {
+                ui.painter().text(
+                    ui.max_rect().right_top(),
+                    egui::Align2::RIGHT_TOP,
+                    code,
+                    egui::FontId::monospace(10.0),
+                    egui::Color32::from_white_alpha(60),
+                );
             }
-            _ => (),
-        };
-    }
-}
 
-struct KeyHistoryItem {
-    key_s: String,
-    modifiers: Modifiers,
-}
+            if self.no_persist {
+                ui.painter().text(
+                    ui.max_rect().left_top(),
+                    egui::Align2::LEFT_TOP,
+                    "🔒 not recorded",
+                    egui::FontId::monospace(10.0),
+                    egui::Color32::from_rgba_unmultiplied(120, 220, 120, 120),
+                );
+            }
 
-struct App {
-    rx: Receiver<InputEvent>,
-    xkb: Xkb,
-    pressed_keycodes: VecDeque<KeyHistoryItem>,
-    rendered_keycodes: Vec<String>,
-    current_modifier_state: Modifiers,
-}
+            if self.mode == Mode::KroTest {
+                self.render_kro_test(ui);
+                return;
+            }
 
-impl App {
-    fn new(
-        cc: &eframe::CreationContext<'_>,
-        rx: Receiver<InputEvent>,
-        tx: Sender<egui::Context>,
-        xkb: Xkb,
-    ) -> Self {
-        tx.send(cc.egui_ctx.clone()).unwrap();
-        cc.egui_ctx
-            .style_mut(|style| style.visuals.window_fill = egui::Color32::TRANSPARENT);
-        cc.egui_ctx.style_mut(|style| {
-            style.visuals.panel_fill = egui::Color32::from_rgba_premultiplied(0, 0, 0, 127)
-        });
+            if let Some(search) = &mut self.search {
+                ui.add(egui::TextEdit::singleline(search).hint_text("Search history"));
+            }
 
-        App {
-            rx,
-            pressed_keycodes: VecDeque::new(),
-            rendered_keycodes: Vec::new(),
-            current_modifier_state: Modifiers {
-                ctrl: false,
-                shift: false,
-                alt: false,
-                sup: false,
-            },
-            xkb,
-        }
-    }
+            ui.with_layout(self.history_layout(), |ui| {
+                let now = self.clock.timestamp();
+                for grid in [&self.dpad, &self.key_grid].into_iter().flatten() {
+                    grid.render(ui, &self.held_keys, now, &self.theme);
+                }
 
-    fn process_input_event(&mut self, event: &InputEvent) {
-        let press_state = match event_press_state(event) {
-            Some(v) => v,
-            None => return,
-        };
+                // Whoever holds the key for a demonstration wants to see the chord being built up
+                if self.held_keys_hud || self.show_on_hold.is_some() {
+                    let label_text = RichText::new(self.held_keys_text())
+                        .family(FontFamily::Monospace)
+                        .color(self.theme.text)
+                        .size(self.font_size);
 
-        let keypress = match self.xkb.push_keycode(event.event.code, &press_state) {
-            Some(v) => v,
-            None => return,
-        };
+                    ui.label(label_text);
+                }
 
-        self.current_modifier_state.update(&keypress, &press_state);
+                if let Some(nudge) = &nudge {
+                    let label_text = RichText::new(nudge)
+                        .family(FontFamily::Monospace)
+                        .color(egui::Color32::from_rgb(255, 160, 160))
+                        .size(self.font_size);
 
-        let key_s = match keypress {
-            KeyPress::Other(s) => {
-                if !is_keydown(&press_state) {
-                    return;
+                    ui.label(label_text);
                 }
-                s
-            }
-            _ => return,
-        };
 
-        // From this point on we know it is a key down of a non-modifier key
+                if let Some(silent_for) = silent_for {
+                    let text = format!(
+                        "No input for {}s, captured elsewhere?",
+                        silent_for.as_secs()
+                    );
+                    let label_text = RichText::new(text)
+                        .family(FontFamily::Monospace)
+                        .color(egui::Color32::YELLOW)
+                        .size(self.font_size);
 
-        let key_press_event = KeyHistoryItem {
-            key_s,
-            modifiers: self.current_modifier_state.clone(),
-        };
+                    ui.label(label_text);
+                }
 
-        self.pressed_keycodes.push_back(key_press_event);
-        let (rendered_keycodes, last_used_elem) =
-            render_keycodes(self.pressed_keycodes.iter().rev());
+                if self.unknown_keys > 0 {
+                    let text = format!("{} unknown key presses", self.unknown_keys);
+                    let label_text = RichText::new(text)
+                        .family(FontFamily::Monospace)
+                        .color(egui::Color32::GRAY)
+                        .size(self.font_size);
 
-        self.rendered_keycodes = rendered_keycodes;
+                    ui.label(label_text);
+                }
 
-        for _ in last_used_elem..self.pressed_keycodes.len() - 1 {
-            self.pressed_keycodes.pop_front();
-        }
-    }
-}
+                if let Some(counts) = &self.clipboard_counts {
+                    let text = counts
+                        .iter()
+                        .map(|(action, count)| format!("{} {count}", action.icon()))
+                        .collect::<Vec<_>>()
+                        .join("  ");
+                    let label_text = RichText::new(text)
+                        .family(FontFamily::Monospace)
+                        .color(egui::Color32::from_rgb(255, 200, 80))
+                        .size(self.font_size);
 
-impl eframe::App for App {
-    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
-        while let Ok(event) = self.rx.try_recv() {
-            self.process_input_event(&event);
-        }
+                    ui.label(label_text);
+                }
 
-        egui::CentralPanel::default().show(ctx, |ui| {
-            ui.with_layout(egui::Layout::bottom_up(egui::Align::default()), |ui| {
-                let item_it = self.rendered_keycodes.iter();
-                for item in item_it {
-                    let label_text = RichText::new(item)
+                if let Some(segments) = &self.segments {
+                    let label_text = RichText::new(segments.summary(self.clock.now()))
                         .family(FontFamily::Monospace)
-                        .color(egui::Color32::WHITE)
-                        .size(15.0);
+                        .color(egui::Color32::from_rgb(160, 200, 255))
+                        .size(self.font_size);
 
                     ui.label(label_text);
+                    ctx.request_repaint_after(segments.next_tick(self.clock.now()));
+                }
+
+                if self.mode == Mode::Keyboard {
+                    return;
+                }
+
+                if self.horizontal {
+                    // Items are newest first, so laying out right to left pushes old ones off the
+                    // left edge
+                    let row_size = egui::vec2(ui.available_width(), self.font_size * 1.5);
+                    ui.allocate_ui_with_layout(
+                        row_size,
+                        egui::Layout::right_to_left(egui::Align::Center),
+                        |ui| {
+                            ui.spacing_mut().item_spacing.x = self.font_size;
+                            self.render_history(ui, &self.rendered_keycodes);
+                        },
+                    );
+                } else if self.columns == 1 {
+                    self.render_history(ui, &self.rendered_keycodes);
+                } else {
+                    // Newest column on the left, each filled like a single column
+                    let column_width = ui.available_width() / self.columns as f32;
+                    let column_align = match self.grows_down() {
+                        true => egui::Align::Min,
+                        false => egui::Align::Max,
+                    };
+                    ui.with_layout(egui::Layout::left_to_right(column_align), |ui| {
+                        for column in self.rendered_keycodes.chunks(self.max_lines) {
+                            ui.allocate_ui_with_layout(
+                                egui::vec2(column_width, ui.available_height()),
+                                self.history_layout(),
+                                |ui| self.render_history(ui, column),
+                            );
+                        }
+                    });
                 }
             });
         });
+
+        // Push everything we drew down out of the window while transitioning. The central panel is
+        // on the background layer, toasts and the health panel are areas
+        let offset = egui::vec2(0.0, (1.0 - visibility) * ctx.screen_rect().height());
+        let mut layers = ctx.memory(|m| m.layer_ids().collect::<Vec<_>>());
+        layers.extend([egui::LayerId::background(), reactions::layer()]);
+        for layer in layers {
+            ctx.translate_layer(layer, offset);
+        }
+    }
+
+    fn on_exit(&mut self, _gl: Option<&eframe::glow::Context>) {
+        if let Some(stats) = &mut self.stats {
+            if let Err(e) = stats.write(self.chord_timings.as_ref()) {
+                eprintln!("Failed to save stats: {e:?}");
+            }
+        }
+
+        if let Some(segments) = &mut self.segments {
+            if let Err(e) = segments.finish(self.clock.now()) {
+                eprintln!("Failed to save segment: {e:?}");
+            }
+        }
     }
 
     fn clear_color(&self, _visuals: &egui::Visuals) -> [f32; 4] {
-        [0.0, 0.0, 0.0, 0.0]
+        match self.opaque {
+            true => self.theme.panel_fill(true).to_normalized_gamma_f32(),
+            false => [0.0, 0.0, 0.0, 0.0],
+        }
     }
 }
 
 fn is_same_key_chord(a: &KeyHistoryItem, b: &KeyHistoryItem) -> bool {
-    a.key_s == b.key_s && a.modifiers == b.modifiers
+    a.key_s == b.key_s && a.modifiers == b.modifiers && a.app == b.app && a.expanded == b.expanded
 }
 
-fn render_item(item: &KeyHistoryItem, count: &usize) -> String {
+fn render_item(item: &KeyHistoryItem, count: &usize, symbols: &Symbols) -> RenderedItem {
     let count_str = if *count > 1 {
         format!("x{}", count)
     } else {
         "".to_string()
     };
 
-    let mut modifier_str = String::new();
-    if item.modifiers.alt {
-        modifier_str.push_str("Alt + ");
-    }
-    if item.modifiers.sup {
-        modifier_str.push_str("Super + ");
+    let mut chord_str = symbols.chord(&item.modifiers, &item.key_s);
+    let chord_len = chord_str.len();
+    if let Some(held_for) = item.held_for {
+        chord_str.push_str(&format!(" ({:.1}s)", held_for.as_secs_f32()));
     }
-    if item.modifiers.ctrl {
-        modifier_str.push_str("Ctrl + ");
+    if item.repeated {
+        chord_str.push_str(" …held");
     }
-    if item.modifiers.shift {
-        modifier_str.push_str("Shift + ");
+
+    let paste_str = match item.pasted_chars {
+        Some(chars) => format!(" — {chars} chars"),
+        None => "".to_string(),
+    };
+
+    if let Some(keys) = item.expanded {
+        let text = format!("✎ expanded snippet ({keys} keys) {count_str}");
+        return RenderedItem {
+            details: text.clone(),
+            keys: Vec::new(),
+            text,
+            count: *count,
+            action: None,
+            last_pressed: item.pressed_at,
+            app: item.app.clone(),
+        };
     }
 
-    format!("{}{} {}", modifier_str, item.key_s, count_str)
+    // Annotations replace the label of special chords, but keep their icon
+    let text = match (item.action, &item.annotation) {
+        (Some(action), annotation) => format!(
+            "{}  {} {}{} {}",
+            chord_str,
+            action.icon(),
+            annotation.as_deref().unwrap_or(action.label()),
+            paste_str,
+            count_str
+        ),
+        (None, Some(annotation)) => format!("{}  → {} {}", chord_str, annotation, count_str),
+        (None, None) => format!("{} {}", chord_str, count_str),
+    };
+
+    // Keys grouped by --chord-window get a cap each
+    let mut keys = symbols.chord_keys(&item.modifiers, &item.key_s);
+    keys.pop();
+    keys.extend(item.key_s.split(" + ").map(|key| symbols.get(key)));
+    let keys = keys.into_iter().map(str::to_string).collect();
+
+    RenderedItem {
+        details: text[chord_len..].trim().to_string(),
+        keys,
+        text,
+        count: *count,
+        action: item.action,
+        last_pressed: item.pressed_at,
+        app: item.app.clone(),
+    }
 }
 
+// evdev value for autorepeat of a held key
+const KEY_REPEAT: i32 = 2;
+
 fn event_press_state(event: &InputEvent) -> Option<KeyPressState> {
     const UP: i32 = KeyPressState::Up as i32;
     const DOWN: i32 = KeyPressState::Down as i32;
@@ -342,37 +3513,274 @@ fn is_keydown(press_state: &KeyPressState) -> bool {
     *press_state == KeyPressState::Down
 }
 
+// Collapses repeated chords, newest first. Returns the rendered lines and how many items of the
+// history were needed to render them, anything older can be dropped
 fn render_keycodes<'a, It: Iterator<Item = &'a KeyHistoryItem>>(
-    key_history: It,
-) -> (Vec<String>, usize) {
-    let mut key_history = key_history.enumerate();
+    mut key_history: It,
+    max_lines: usize,
+    symbols: &Symbols,
+) -> (Vec<RenderedItem>, usize) {
     let mut ret = Vec::new();
 
+    if max_lines == 0 {
+        return (ret, 0);
+    }
+
     let mut last_item = match key_history.next() {
-        Some((_, v)) => v,
+        Some(v) => v,
         None => return (ret, 0),
     };
     let mut last_item_count = 1;
-    let mut last_elem_idx = 1;
-
-    const MAX_LINES: usize = 40;
-    for (i, item) in key_history {
-        last_elem_idx = i;
-        if ret.len() > MAX_LINES {
-            return (ret, last_elem_idx);
-        }
+    let mut used_items = 1;
+    // History is iterated newest first
+    let mut last_pressed = last_item.pressed_at;
 
+    for item in key_history {
         if is_same_key_chord(item, last_item) {
             last_item_count += 1;
         } else {
-            ret.push(render_item(last_item, &last_item_count));
+            ret.push(RenderedItem {
+                last_pressed,
+                ..render_item(last_item, &last_item_count, symbols)
+            });
+            if ret.len() == max_lines {
+                return (ret, used_items);
+            }
             last_item_count = 1;
+            last_pressed = item.pressed_at;
         }
 
         last_item = item;
+        used_items += 1;
+    }
+
+    ret.push(RenderedItem {
+        last_pressed,
+        ..render_item(last_item, &last_item_count, symbols)
+    });
+
+    (ret, used_items)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use input_bindings::*;
+    use proptest::prelude::*;
+
+    fn make_history(keys: &[(u8, bool)]) -> Vec<KeyHistoryItem> {
+        keys.iter()
+            .map(|(key, ctrl)| KeyHistoryItem {
+                key_s: key.to_string(),
+                modifiers: Modifiers {
+                    ctrl: *ctrl,
+                    ..Default::default()
+                },
+                action: None,
+                annotation: None,
+                pasted_chars: None,
+                held_for: None,
+                repeated: false,
+                expanded: None,
+                pressed_at: Instant::now(),
+                app: None,
+            })
+            .collect()
+    }
+
+    // Small alphabet so that runs of the same chord are common
+    fn history_strategy() -> impl Strategy<Value = Vec<(u8, bool)>> {
+        prop::collection::vec((0u8..3, any::<bool>()), 0..200)
+    }
+
+    fn texts(rendered: &[RenderedItem]) -> Vec<String> {
+        rendered.iter().map(|r| r.text.clone()).collect()
+    }
+
+    // Presses and releases each key in turn
+    fn taps(codes: &[u32]) -> Vec<(u32, bool)> {
+        codes
+            .iter()
+            .flat_map(|code| [(*code, true), (*code, false)])
+            .collect()
+    }
+
+    // Holds the keys down in order, then lets go of them in reverse
+    fn chord(codes: &[u32]) -> Vec<(u32, bool)> {
+        let mut ret = codes.iter().map(|code| (*code, true)).collect::<Vec<_>>();
+        ret.extend(codes.iter().rev().map(|code| (*code, false)));
+        ret
+    }
+
+    // Decodes the events through a keymap compiled from the installed xkeyboard-config, the same
+    // way the reader does, and returns the history line of every key press
+    fn decode(layout: &str, variant: Option<&str>, events: &[(u32, bool)]) -> Vec<String> {
+        let names = RuleNames {
+            rules: Some("evdev".to_string()),
+            model: Some("pc105".to_string()),
+            layout: Some(layout.to_string()),
+            variant: variant.map(str::to_string),
+            options: None,
+        };
+        let mut xkb =
+            Xkb::from_source(&KeymapSource::Names(names)).expect("Failed to compile keymap");
+        let mut modifiers = Modifiers::default();
+
+        let mut ret = Vec::new();
+        for (code, down) in events {
+            let press_state = match down {
+                true => KeyPressState::Down,
+                false => KeyPressState::Up,
+            };
+            let keypress = match xkb.push_keycode(*code as u16, &press_state) {
+                Some(v) => v,
+                None => continue,
+            };
+            modifiers.update(&keypress, &press_state);
+
+            if let (KeyPress::Other(key_s), true) = (keypress, down) {
+                let item = KeyHistoryItem {
+                    key_s,
+                    modifiers: modifiers.clone(),
+                    action: None,
+                    annotation: None,
+                    pasted_chars: None,
+                    held_for: None,
+                    repeated: false,
+                    expanded: None,
+                    pressed_at: Instant::now(),
+                    app: None,
+                };
+                ret.push(render_item(&item, &1, &Symbols::plain()).text);
+            }
+        }
+        ret
+    }
+
+    #[test]
+    fn golden_us() {
+        let mut events = taps(&[KEY_Q, KEY_W, KEY_Y, KEY_Z, KEY_1, KEY_SEMICOLON]);
+        events.extend(chord(&[KEY_LEFTCTRL, KEY_C]));
+        events.extend(chord(&[KEY_LEFTCTRL, KEY_LEFTSHIFT, KEY_T]));
+        events.extend(chord(&[KEY_LEFTSHIFT, KEY_1]));
+        assert_eq!(
+            decode("us", None, &events),
+            [
+                "q ",
+                "w ",
+                "y ",
+                "z ",
+                "1 ",
+                "; ",
+                "Ctrl + c ",
+                "Ctrl + Shift + T ",
+                "Shift + ! "
+            ]
+        );
+    }
+
+    #[test]
+    fn golden_de() {
+        let mut events = taps(&[KEY_Y, KEY_Z, KEY_SEMICOLON, KEY_MINUS, KEY_102ND]);
+        // AltGr shows up as a key of its own, the @ it selects without it
+        events.extend(chord(&[KEY_RIGHTALT, KEY_Q]));
+        assert_eq!(
+            decode("de", None, &events),
+            ["z ", "y ", "ö ", "ß ", "< ", "ISO_Level3_Shift ", "@ "]
+        );
+    }
+
+    #[test]
+    fn golden_fr_azerty() {
+        let mut events = taps(&[KEY_Q, KEY_A, KEY_W, KEY_Z, KEY_SEMICOLON, KEY_1]);
+        events.extend(chord(&[KEY_LEFTSHIFT, KEY_1]));
+        assert_eq!(
+            decode("fr", None, &events),
+            ["a ", "q ", "z ", "w ", "m ", "& ", "Shift + 1 "]
+        );
+    }
+
+    #[test]
+    fn golden_neo() {
+        let mut events = taps(&[KEY_Q, KEY_W, KEY_E, KEY_A, KEY_S]);
+        // Mod3 on caps lock gives the symbol layer. Level shifts aren't modifiers to the overlay
+        events.extend(chord(&[KEY_CAPSLOCK, KEY_E]));
+        assert_eq!(
+            decode("de", Some("neo"), &events),
+            ["x ", "v ", "l ", "u ", "i ", "ISO_Level3_Shift ", "[ "]
+        );
+    }
+
+    #[test]
+    fn golden_jp() {
+        let events = taps(&[KEY_Q, KEY_RO, KEY_YEN, KEY_LEFTBRACE, KEY_APOSTROPHE]);
+        assert_eq!(
+            decode("jp", None, &events),
+            ["q ", "\\ ", "\\ ", "@ ", ": "]
+        );
+    }
+
+    #[test]
+    fn parse_secs_rejects_invalid() {
+        let parse = |s: &str| parse_secs(Some(s.to_string()), ArgParseError::InvalidHoldDuration);
+
+        assert_eq!(parse("0.5").ok(), Some(Duration::from_millis(500)));
+        assert_eq!(parse("0").ok(), Some(Duration::ZERO));
+        for s in ["-1", "inf", "-inf", "NaN", "1e30", "", "1s"] {
+            assert!(parse(s).is_err(), "{s} was accepted");
+        }
+        assert!(parse_secs(None, ArgParseError::InvalidHoldDuration).is_err());
     }
 
-    ret.push(render_item(last_item, &last_item_count));
+    #[test]
+    fn parse_rate_rejects_invalid() {
+        let parse = |s: &str| parse_rate(Some(s.to_string()), ArgParseError::InvalidFrameRate);
 
-    (ret, last_elem_idx)
+        assert_eq!(parse("60").ok(), Some(60.0));
+        for s in ["0", "-30", "inf", "NaN", "1e-30", "1e30"] {
+            assert!(parse(s).is_err(), "{s} was accepted");
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn counts_sum_to_used_presses(keys in history_strategy(), max_lines in 0usize..50) {
+            let history = make_history(&keys);
+            let (rendered, used_items) = render_keycodes(history.iter().rev(), max_lines, &Symbols::plain());
+
+            let total: usize = rendered.iter().map(|r| r.count).sum();
+            prop_assert_eq!(total, used_items);
+
+            if rendered.len() < max_lines {
+                prop_assert_eq!(used_items, history.len());
+            }
+        }
+
+        #[test]
+        fn max_lines_never_exceeded(keys in history_strategy(), max_lines in 0usize..50) {
+            let history = make_history(&keys);
+            let (rendered, _) = render_keycodes(history.iter().rev(), max_lines, &Symbols::plain());
+
+            prop_assert!(rendered.len() <= max_lines);
+        }
+
+        #[test]
+        fn pops_never_remove_rendered_items(keys in history_strategy(), max_lines in 1usize..50) {
+            let history = make_history(&keys);
+
+            // Mirror how the app feeds the history, one press at a time with pops in between
+            let mut kept = VecDeque::new();
+            let mut rendered = Vec::new();
+            for item in &history {
+                kept.push_back(item);
+                let used_items;
+                (rendered, used_items) = render_keycodes(kept.iter().rev().copied(), max_lines, &Symbols::plain());
+                let unused_items = kept.len() - used_items;
+                kept.drain(..unused_items);
+            }
+
+            let (expected, _) = render_keycodes(history.iter().rev(), max_lines, &Symbols::plain());
+            prop_assert_eq!(texts(&rendered), texts(&expected));
+        }
+    }
 }
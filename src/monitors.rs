@@ -0,0 +1,122 @@
+use std::{
+    sync::mpsc::{self, Receiver, Sender},
+    thread,
+};
+
+use eframe::egui::{self, Pos2, Rect, Vec2};
+use x11rb::{
+    connection::Connection,
+    errors::{ConnectError, ConnectionError, ReplyError},
+    protocol::{
+        randr::{self, ConnectionExt as _},
+        xproto::ConnectionExt as _,
+        Event,
+    },
+    rust_connection::RustConnection,
+};
+
+#[derive(Debug)]
+pub enum MonitorError {
+    Connect(ConnectError),
+    Connection(ConnectionError),
+    Reply(ReplyError),
+    NotFound(String),
+}
+
+pub struct Monitor {
+    pub name: String,
+    // In physical pixels on the X screen
+    pub rect: Rect,
+}
+
+// Monitors in the order xrandr --listmonitors numbers them. eframe can't tell us about monitors
+// before the window exists, so ask the X server
+fn list() -> Result<Vec<Monitor>, MonitorError> {
+    let (conn, screen_num) = x11rb::connect(None).map_err(MonitorError::Connect)?;
+    let root = conn.setup().roots[screen_num].root;
+
+    let reply = conn
+        .randr_get_monitors(root, true)
+        .map_err(MonitorError::Connection)?
+        .reply()
+        .map_err(MonitorError::Reply)?;
+
+    let mut ret = Vec::new();
+    for info in reply.monitors {
+        let name = conn
+            .get_atom_name(info.name)
+            .map_err(MonitorError::Connection)?
+            .reply()
+            .map_err(MonitorError::Reply)?
+            .name;
+
+        ret.push(Monitor {
+            name: String::from_utf8_lossy(&name).into_owned(),
+            rect: Rect::from_min_size(
+                Pos2::new(info.x as f32, info.y as f32),
+                Vec2::new(info.width as f32, info.height as f32),
+            ),
+        });
+    }
+
+    Ok(ret)
+}
+
+// Selector is either the monitor's number or its output name, e.g. 1 or HDMI-1
+pub fn find(selector: &str) -> Result<Monitor, MonitorError> {
+    let mut monitors = list()?;
+
+    let idx = match selector.parse::<usize>() {
+        Ok(i) => Some(i),
+        Err(_) => monitors.iter().position(|m| m.name == selector),
+    };
+
+    match idx {
+        Some(i) if i < monitors.len() => Ok(monitors.swap_remove(i)),
+        _ => Err(MonitorError::NotFound(selector.to_string())),
+    }
+}
+
+// Looks the monitor up again whenever outputs or resolutions change, so the overlay follows it
+// instead of keeping the geometry it had at startup. None while the monitor is disconnected
+pub fn spawn_watcher(
+    selector: String,
+    rx: Receiver<egui::Context>,
+) -> Result<Receiver<Option<Monitor>>, MonitorError> {
+    let (conn, screen_num) = x11rb::connect(None).map_err(MonitorError::Connect)?;
+    let root = conn.setup().roots[screen_num].root;
+
+    conn.randr_select_input(
+        root,
+        randr::NotifyMask::SCREEN_CHANGE | randr::NotifyMask::CRTC_CHANGE,
+    )
+    .map_err(MonitorError::Connection)?
+    .check()
+    .map_err(MonitorError::Reply)?;
+
+    let (tx, app_rx) = mpsc::channel();
+    thread::spawn(move || watcher_thread(conn, selector, tx, rx));
+    Ok(app_rx)
+}
+
+fn watcher_thread(
+    conn: RustConnection,
+    selector: String,
+    tx: Sender<Option<Monitor>>,
+    rx: Receiver<egui::Context>,
+) {
+    let ctx = rx.recv().unwrap();
+
+    loop {
+        match conn.wait_for_event() {
+            Ok(Event::RandrScreenChangeNotify(_) | Event::RandrNotify(_)) => (),
+            Ok(_) => continue,
+            Err(_) => return,
+        }
+
+        if tx.send(find(&selector).ok()).is_err() {
+            return;
+        }
+        ctx.request_repaint();
+    }
+}
@@ -0,0 +1,61 @@
+use super::input_bindings;
+
+// Mouse buttons and wheel movement, shown in the history like keys with --mouse
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum MouseInput {
+    LeftClick,
+    RightClick,
+    MiddleClick,
+    BackClick,
+    ForwardClick,
+    ScrollUp,
+    ScrollDown,
+    ScrollLeft,
+    ScrollRight,
+}
+
+impl MouseInput {
+    pub fn from_button(code: u16) -> Option<MouseInput> {
+        let ret = match code as u32 {
+            input_bindings::BTN_LEFT => MouseInput::LeftClick,
+            input_bindings::BTN_RIGHT => MouseInput::RightClick,
+            input_bindings::BTN_MIDDLE => MouseInput::MiddleClick,
+            input_bindings::BTN_SIDE => MouseInput::BackClick,
+            input_bindings::BTN_EXTRA => MouseInput::ForwardClick,
+            _ => return None,
+        };
+
+        Some(ret)
+    }
+
+    // Only the low resolution wheel events, high resolution wheels report both
+    pub fn from_scroll(event: &input_bindings::input_event) -> Option<MouseInput> {
+        if event.type_ != input_bindings::EV_REL as u16 {
+            return None;
+        }
+
+        let ret = match (event.code as u32, event.value.signum()) {
+            (input_bindings::REL_WHEEL, 1) => MouseInput::ScrollUp,
+            (input_bindings::REL_WHEEL, -1) => MouseInput::ScrollDown,
+            (input_bindings::REL_HWHEEL, 1) => MouseInput::ScrollRight,
+            (input_bindings::REL_HWHEEL, -1) => MouseInput::ScrollLeft,
+            _ => return None,
+        };
+
+        Some(ret)
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            MouseInput::LeftClick => "LeftClick",
+            MouseInput::RightClick => "RightClick",
+            MouseInput::MiddleClick => "MiddleClick",
+            MouseInput::BackClick => "BackClick",
+            MouseInput::ForwardClick => "ForwardClick",
+            MouseInput::ScrollUp => "Scroll Up",
+            MouseInput::ScrollDown => "Scroll Down",
+            MouseInput::ScrollLeft => "Scroll Left",
+            MouseInput::ScrollRight => "Scroll Right",
+        }
+    }
+}
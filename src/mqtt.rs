@@ -0,0 +1,110 @@
+use std::{
+    io::{Error as IoError, ErrorKind, Read, Result as IoResult, Write},
+    net::TcpStream,
+    sync::mpsc::{self, Receiver, Sender},
+    thread,
+};
+
+use super::toast::Toast;
+
+// Just enough of MQTT 3.1.1 to publish QoS 0 messages
+const CONNECT: u8 = 0x10;
+const CONNACK: u8 = 0x20;
+const PUBLISH: u8 = 0x30;
+
+fn push_remaining_length(buf: &mut Vec<u8>, mut len: usize) {
+    loop {
+        let mut byte = (len % 128) as u8;
+        len /= 128;
+        if len > 0 {
+            byte |= 0x80;
+        }
+        buf.push(byte);
+
+        if len == 0 {
+            return;
+        }
+    }
+}
+
+fn push_string(buf: &mut Vec<u8>, s: &[u8]) {
+    buf.extend((s.len() as u16).to_be_bytes());
+    buf.extend(s);
+}
+
+fn packet(packet_type: u8, body: &[u8]) -> Vec<u8> {
+    let mut ret = vec![packet_type];
+    push_remaining_length(&mut ret, body.len());
+    ret.extend(body);
+    ret
+}
+
+fn connect(broker: &str) -> IoResult<TcpStream> {
+    let mut stream = TcpStream::connect(broker)?;
+
+    let mut body = Vec::new();
+    push_string(&mut body, b"MQTT");
+    // Protocol level 4, clean session, no keep alive so we don't need to ping while idle
+    body.extend([4, 0x02, 0, 0]);
+    push_string(
+        &mut body,
+        format!("keyboard-overlay-{}", std::process::id()).as_bytes(),
+    );
+    stream.write_all(&packet(CONNECT, &body))?;
+
+    let mut connack = [0u8; 4];
+    stream.read_exact(&mut connack)?;
+    if connack[0] != CONNACK || connack[3] != 0 {
+        return Err(IoError::new(
+            ErrorKind::ConnectionRefused,
+            format!("broker refused connection ({})", connack[3]),
+        ));
+    }
+
+    Ok(stream)
+}
+
+fn publish(stream: &mut TcpStream, topic: &str, payload: &str) -> IoResult<()> {
+    let mut body = Vec::new();
+    push_string(&mut body, topic.as_bytes());
+    body.extend(payload.as_bytes());
+    stream.write_all(&packet(PUBLISH, &body))
+}
+
+pub fn spawn_publisher(broker: String, topic: String, toast_tx: Sender<Toast>) -> Sender<String> {
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || publisher_thread(rx, broker, topic, toast_tx));
+    tx
+}
+
+fn publisher_thread(rx: Receiver<String>, broker: String, topic: String, toast_tx: Sender<Toast>) {
+    let mut stream = None;
+    // Only report the first of a run of failed connections, not one per key press
+    let mut failing = false;
+
+    while let Ok(payload) = rx.recv() {
+        // Connect lazily and reconnect after errors, brokers restart and wifi drops
+        if stream.is_none() {
+            match connect(&broker) {
+                Ok(v) => {
+                    stream = Some(v);
+                    failing = false;
+                }
+                Err(e) => {
+                    if !failing {
+                        let _ = toast_tx.send(Toast::error(format!("MQTT connect failed: {e}")));
+                    }
+                    failing = true;
+                    continue;
+                }
+            }
+        }
+
+        if let Some(s) = &mut stream {
+            if let Err(e) = publish(s, &topic, &payload) {
+                let _ = toast_tx.send(Toast::error(format!("MQTT publish failed: {e}")));
+                stream = None;
+            }
+        }
+    }
+}
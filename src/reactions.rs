@@ -0,0 +1,71 @@
+use std::time::{Duration, Instant};
+
+use eframe::egui::{self, Align2, Color32, FontId, Order, Vec2};
+
+use super::{chord::Chord, KeyHistoryItem};
+
+const REACTION_TIME: Duration = Duration::from_millis(1500);
+// Font size of the reaction when it first appears, it grows by half over REACTION_TIME
+const REACTION_SIZE: f32 = 96.0;
+
+// Large emoji shown in the middle of the overlay when their chord is pressed, e.g. F13 → 🎉
+pub struct Reactions {
+    table: Vec<(Chord, String)>,
+    // Newest reaction replaces the one being shown
+    active: Option<(String, Instant)>,
+}
+
+// Drawn above the history, whatever its anchor
+pub fn layer() -> egui::LayerId {
+    egui::LayerId::new(Order::Foreground, egui::Id::new("reaction"))
+}
+
+impl Reactions {
+    pub fn new(table: Vec<(Chord, String)>) -> Reactions {
+        Reactions {
+            table,
+            active: None,
+        }
+    }
+
+    // Returns whether the item was a reaction chord
+    pub fn trigger(&mut self, item: &KeyHistoryItem, now: Instant) -> bool {
+        let reaction = self
+            .table
+            .iter()
+            .find(|(chord, _)| chord.matches(item))
+            .map(|(_, reaction)| reaction.clone());
+
+        match reaction {
+            Some(v) => {
+                self.active = Some((v, now));
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn render(&mut self, ctx: &egui::Context, frame: Duration, now: Instant) {
+        let (reaction, started) = match &self.active {
+            Some(v) => v,
+            None => return,
+        };
+
+        let progress =
+            now.saturating_duration_since(*started).as_secs_f32() / REACTION_TIME.as_secs_f32();
+        if progress >= 1.0 {
+            self.active = None;
+            return;
+        }
+
+        ctx.layer_painter(layer()).text(
+            ctx.screen_rect().center() + Vec2::new(0.0, -20.0 * progress),
+            Align2::CENTER_CENTER,
+            reaction,
+            FontId::proportional(REACTION_SIZE * (1.0 + progress / 2.0)),
+            Color32::WHITE.gamma_multiply(1.0 - progress * progress),
+        );
+
+        ctx.request_repaint_after(frame);
+    }
+}
@@ -0,0 +1,101 @@
+use std::time::{Duration, Instant};
+
+use eframe::egui::{self, Color32, Pos2, Stroke, Vec2, ViewportBuilder, ViewportId};
+use x11rb::{
+    connection::Connection,
+    errors::ConnectError,
+    protocol::xproto::{ConnectionExt as _, Window},
+    rust_connection::RustConnection,
+};
+
+const RIPPLE_TIME: Duration = Duration::from_millis(400);
+// Diameter of the ring when it's done expanding
+const RIPPLE_SIZE: f32 = 80.0;
+
+#[derive(Debug)]
+pub enum RippleError {
+    Connect(ConnectError),
+}
+
+struct Ripple {
+    id: u64,
+    // In physical pixels on the X screen
+    center: Pos2,
+    started: Instant,
+}
+
+// Rings drawn around the pointer on click. The overlay window doesn't cover the screen, so each
+// ring gets its own small window at the pointer
+pub struct Ripples {
+    conn: RustConnection,
+    root: Window,
+    active: Vec<Ripple>,
+    next_id: u64,
+}
+
+impl Ripples {
+    pub fn connect() -> Result<Ripples, RippleError> {
+        let (conn, screen_num) = x11rb::connect(None).map_err(RippleError::Connect)?;
+        let root = conn.setup().roots[screen_num].root;
+
+        Ok(Ripples {
+            conn,
+            root,
+            active: Vec::new(),
+            next_id: 0,
+        })
+    }
+
+    pub fn click(&mut self) {
+        let pointer = match self.conn.query_pointer(self.root) {
+            Ok(cookie) => cookie.reply(),
+            Err(_) => return,
+        };
+        let pointer = match pointer {
+            Ok(v) => v,
+            Err(_) => return,
+        };
+
+        self.active.push(Ripple {
+            id: self.next_id,
+            center: Pos2::new(pointer.root_x as f32, pointer.root_y as f32),
+            started: Instant::now(),
+        });
+        self.next_id += 1;
+    }
+
+    pub fn render(&mut self, ctx: &egui::Context, color: Color32, frame: Duration) {
+        self.active.retain(|r| r.started.elapsed() < RIPPLE_TIME);
+        if self.active.is_empty() {
+            return;
+        }
+
+        let pixels_per_point = ctx.input(|i| i.viewport().native_pixels_per_point.unwrap_or(1.0));
+        for ripple in &self.active {
+            let progress = ripple.started.elapsed().as_secs_f32() / RIPPLE_TIME.as_secs_f32();
+            let builder = ViewportBuilder::default()
+                .with_position(ripple.center / pixels_per_point - Vec2::splat(RIPPLE_SIZE / 2.0))
+                .with_inner_size(Vec2::splat(RIPPLE_SIZE))
+                .with_transparent(true)
+                .with_decorations(false)
+                .with_resizable(false)
+                .with_active(false)
+                .with_always_on_top()
+                .with_mouse_passthrough(true);
+
+            let id = ViewportId::from_hash_of(("ripple", ripple.id));
+            ctx.show_viewport_immediate(id, builder, |ctx, _| {
+                egui::CentralPanel::default()
+                    .frame(egui::Frame::none())
+                    .show(ctx, |ui| {
+                        let radius = (RIPPLE_SIZE / 2.0 - 2.0) * progress.max(0.1);
+                        let stroke = Stroke::new(3.0, color.gamma_multiply(1.0 - progress));
+                        ui.painter()
+                            .circle_stroke(ui.max_rect().center(), radius, stroke);
+                    });
+            });
+        }
+
+        ctx.request_repaint_after(frame);
+    }
+}
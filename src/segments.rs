@@ -0,0 +1,139 @@
+use std::{
+    collections::BTreeMap,
+    fs,
+    io::Error as IoError,
+    path::PathBuf,
+    time::{Duration, Instant},
+};
+
+use toml_edit::{value, Document, Item, Table};
+
+use super::{symbols::Symbols, KeyHistoryItem, Modifiers};
+
+#[derive(Debug)]
+pub enum SegmentError {
+    Write(IoError),
+}
+
+struct Segment {
+    name: String,
+    started: Instant,
+    keys: u64,
+    chords: u64,
+    // By chord text, e.g. "Ctrl + c"
+    counts: BTreeMap<String, i64>,
+}
+
+impl Segment {
+    fn new(name: String, now: Instant) -> Segment {
+        Segment {
+            name,
+            started: now,
+            keys: 0,
+            chords: 0,
+            counts: BTreeMap::new(),
+        }
+    }
+
+    fn to_toml(&self, session_code: Option<&str>, now: Instant) -> String {
+        let mut counts = Table::new();
+        for (chord, count) in &self.counts {
+            counts.insert(chord, value(*count));
+        }
+
+        let mut doc = Document::new();
+        doc["name"] = value(self.name.as_str());
+        if let Some(code) = session_code {
+            doc["session_code"] = value(code);
+        }
+        doc["duration_secs"] = value(now.saturating_duration_since(self.started).as_secs_f64());
+        doc["keys"] = value(self.keys as i64);
+        doc["chords"] = value(self.chords as i64);
+        doc["counts"] = Item::Table(counts);
+        doc.to_string()
+    }
+}
+
+// Splits the session into named parts, e.g. "intro" and "demo", each with its own stats.
+// Finished segments are written to export_dir as 01-intro.toml, 02-demo.toml, ...
+pub struct Segments {
+    current: Segment,
+    finished: usize,
+    export_dir: Option<PathBuf>,
+    // --watermark code, written to every exported segment
+    session_code: Option<String>,
+}
+
+impl Segments {
+    pub fn new(
+        export_dir: Option<PathBuf>,
+        session_code: Option<String>,
+        now: Instant,
+    ) -> Segments {
+        Segments {
+            current: Segment::new("start".to_string(), now),
+            finished: 0,
+            export_dir,
+            session_code,
+        }
+    }
+
+    pub fn record(&mut self, item: &KeyHistoryItem) {
+        self.current.keys += 1;
+        if item.modifiers != Modifiers::default() {
+            self.current.chords += 1;
+        }
+
+        let chord = Symbols::plain().chord(&item.modifiers, &item.key_s);
+        *self.current.counts.entry(chord).or_default() += 1;
+    }
+
+    // Without a name the segment is numbered
+    pub fn start(&mut self, name: Option<String>, now: Instant) -> Result<(), SegmentError> {
+        let name = name.unwrap_or_else(|| format!("segment {}", self.finished + 2));
+        let previous = std::mem::replace(&mut self.current, Segment::new(name, now));
+        self.finished += 1;
+        self.export(&previous, self.finished, now)
+    }
+
+    // Writes out the segment in progress, e.g. on exit
+    pub fn finish(&mut self, now: Instant) -> Result<(), SegmentError> {
+        self.export(&self.current, self.finished + 1, now)
+    }
+
+    fn export(&self, segment: &Segment, index: usize, now: Instant) -> Result<(), SegmentError> {
+        let dir = match &self.export_dir {
+            Some(v) => v,
+            None => return Ok(()),
+        };
+
+        let file_name: String = segment
+            .name
+            .chars()
+            .map(|c| if c.is_alphanumeric() { c } else { '-' })
+            .collect();
+        let path = dir.join(format!("{index:02}-{file_name}.toml"));
+        fs::write(path, segment.to_toml(self.session_code.as_deref(), now))
+            .map_err(SegmentError::Write)
+    }
+
+    pub fn summary(&self, now: Instant) -> String {
+        let elapsed = now
+            .saturating_duration_since(self.current.started)
+            .as_secs();
+        format!(
+            "● {}  {}:{:02}  {} keys  {} chords",
+            self.current.name,
+            elapsed / 60,
+            elapsed % 60,
+            self.current.keys,
+            self.current.chords
+        )
+    }
+
+    // How long until the elapsed time shown in the summary changes
+    pub fn next_tick(&self, now: Instant) -> Duration {
+        let elapsed = now.saturating_duration_since(self.current.started);
+        Duration::from_secs(elapsed.as_secs() + 1).saturating_sub(elapsed)
+    }
+}
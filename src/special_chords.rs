@@ -0,0 +1,89 @@
+use super::{chord::Chord, KeyHistoryItem};
+
+#[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd)]
+pub enum SpecialAction {
+    Undo,
+    Redo,
+    Cut,
+    Copy,
+    Paste,
+}
+
+impl SpecialAction {
+    pub fn label(&self) -> &'static str {
+        match self {
+            SpecialAction::Undo => "Undo",
+            SpecialAction::Redo => "Redo",
+            SpecialAction::Cut => "Cut",
+            SpecialAction::Copy => "Copy",
+            SpecialAction::Paste => "Paste",
+        }
+    }
+
+    pub fn icon(&self) -> &'static str {
+        match self {
+            SpecialAction::Undo => "↩",
+            SpecialAction::Redo => "↪",
+            SpecialAction::Cut => "✂",
+            SpecialAction::Copy => "📋",
+            SpecialAction::Paste => "📥",
+        }
+    }
+
+    fn default_chords(&self) -> &'static [&'static str] {
+        match self {
+            SpecialAction::Undo => &["Ctrl+z"],
+            SpecialAction::Redo => &["Ctrl+Shift+z", "Ctrl+y"],
+            SpecialAction::Cut => &["Ctrl+x"],
+            SpecialAction::Copy => &["Ctrl+c"],
+            SpecialAction::Paste => &["Ctrl+v"],
+        }
+    }
+
+    pub fn is_clipboard(&self) -> bool {
+        matches!(
+            self,
+            SpecialAction::Cut | SpecialAction::Copy | SpecialAction::Paste
+        )
+    }
+}
+
+const ALL_ACTIONS: [SpecialAction; 5] = [
+    SpecialAction::Undo,
+    SpecialAction::Redo,
+    SpecialAction::Cut,
+    SpecialAction::Copy,
+    SpecialAction::Paste,
+];
+
+pub struct SpecialChords {
+    chords: Vec<(Chord, SpecialAction)>,
+}
+
+impl SpecialChords {
+    // Any action without user provided chords falls back to its defaults
+    pub fn new(mut user_chords: Vec<(Chord, SpecialAction)>) -> SpecialChords {
+        for action in ALL_ACTIONS {
+            if user_chords.iter().any(|(_, a)| *a == action) {
+                continue;
+            }
+
+            let defaults = action
+                .default_chords()
+                .iter()
+                .map(|s| (Chord::parse(s).expect("invalid default chord"), action));
+            user_chords.extend(defaults);
+        }
+
+        SpecialChords {
+            chords: user_chords,
+        }
+    }
+
+    pub fn find(&self, item: &KeyHistoryItem) -> Option<SpecialAction> {
+        self.chords
+            .iter()
+            .find(|(chord, _)| chord.matches(item))
+            .map(|(_, action)| *action)
+    }
+}
@@ -0,0 +1,71 @@
+use std::{
+    process::{Command, Stdio},
+    sync::mpsc::{self, Receiver, Sender},
+    thread,
+    time::{Duration, Instant},
+};
+
+use super::{toast::Toast, KeyHistoryItem};
+
+// Spoken form of a history item, e.g. "Control Shift P"
+pub fn spoken_text(item: &KeyHistoryItem) -> String {
+    let mut words = Vec::new();
+    if item.modifiers.ctrl {
+        words.push("Control".to_string());
+    }
+    if item.modifiers.alt {
+        words.push("Alt".to_string());
+    }
+    if item.modifiers.sup {
+        words.push("Super".to_string());
+    }
+    if item.modifiers.shift {
+        words.push("Shift".to_string());
+    }
+
+    // Single letters are read more clearly in upper case, "a" tends to be read as a word
+    words.push(item.key_s.to_uppercase());
+
+    if let Some(action) = item.action {
+        words.push(action.label().to_string());
+    }
+
+    words.join(" ")
+}
+
+// Announcements go through speech-dispatcher's spd-say. Anything typed while the previous
+// announcement is still being spoken is collapsed into the newest item so speech never lags far
+// behind the keyboard
+pub fn spawn_speaker(min_interval: Duration, toast_tx: Sender<Toast>) -> Sender<String> {
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || speaker_thread(rx, min_interval, toast_tx));
+    tx
+}
+
+fn speaker_thread(rx: Receiver<String>, min_interval: Duration, toast_tx: Sender<Toast>) {
+    let mut last_spoken = None::<Instant>;
+
+    while let Ok(mut text) = rx.recv() {
+        if let Some(last_spoken) = last_spoken {
+            thread::sleep(min_interval.saturating_sub(last_spoken.elapsed()));
+        }
+
+        while let Ok(newer) = rx.try_recv() {
+            text = newer;
+        }
+
+        let status = Command::new("spd-say")
+            .arg("--wait")
+            .arg(&text)
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status();
+
+        if let Err(e) = status {
+            let _ = toast_tx.send(Toast::error(format!("Failed to run spd-say: {e}")));
+            return;
+        }
+
+        last_spoken = Some(Instant::now());
+    }
+}
@@ -0,0 +1,298 @@
+use std::{
+    collections::BTreeMap,
+    fs,
+    io::Error as IoError,
+    path::PathBuf,
+    time::{Duration, Instant},
+};
+
+use super::{
+    chord::Chord, chord_timing::ChordTimings, input_bindings, symbols::Symbols, Modifiers,
+};
+
+// Written this often as well as on exit, so a crash doesn't lose the whole session
+const STATS_INTERVAL: Duration = Duration::from_secs(60);
+
+// Keys typed with the left hand on a touch typed US layout, by evdev code. Everything else left of
+// the arrows is typed with the right, the space bar with either
+const LEFT_HAND: &[u32] = &[
+    input_bindings::KEY_ESC,
+    input_bindings::KEY_F1,
+    input_bindings::KEY_F2,
+    input_bindings::KEY_F3,
+    input_bindings::KEY_F4,
+    input_bindings::KEY_F5,
+    input_bindings::KEY_GRAVE,
+    input_bindings::KEY_1,
+    input_bindings::KEY_2,
+    input_bindings::KEY_3,
+    input_bindings::KEY_4,
+    input_bindings::KEY_5,
+    input_bindings::KEY_TAB,
+    input_bindings::KEY_Q,
+    input_bindings::KEY_W,
+    input_bindings::KEY_E,
+    input_bindings::KEY_R,
+    input_bindings::KEY_T,
+    input_bindings::KEY_CAPSLOCK,
+    input_bindings::KEY_A,
+    input_bindings::KEY_S,
+    input_bindings::KEY_D,
+    input_bindings::KEY_F,
+    input_bindings::KEY_G,
+    input_bindings::KEY_LEFTSHIFT,
+    input_bindings::KEY_102ND,
+    input_bindings::KEY_Z,
+    input_bindings::KEY_X,
+    input_bindings::KEY_C,
+    input_bindings::KEY_V,
+    input_bindings::KEY_B,
+    input_bindings::KEY_LEFTCTRL,
+    input_bindings::KEY_LEFTMETA,
+    input_bindings::KEY_LEFTALT,
+];
+
+#[derive(Clone, Copy, PartialEq)]
+enum Hand {
+    Left,
+    Right,
+}
+
+impl Hand {
+    fn of(code: u16) -> Option<Hand> {
+        let code = code as u32;
+        if LEFT_HAND.contains(&code) {
+            Some(Hand::Left)
+        } else if code == input_bindings::KEY_SPACE || code >= input_bindings::BTN_MISC {
+            // Mouse buttons aren't typed with either hand of the keyboard
+            None
+        } else {
+            Some(Hand::Right)
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Hand::Left => "left",
+            Hand::Right => "right",
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum StatsError {
+    Write(IoError),
+}
+
+// Session counts for --stats-out. Written as CSV if the path ends in .csv, JSON otherwise
+pub struct Stats {
+    path: PathBuf,
+    // --watermark code, so the stats can be matched to the recording
+    session_code: Option<String>,
+    started: Instant,
+    // By key name, e.g. "a"
+    keys: BTreeMap<String, u64>,
+    // By chord text, only chords with a modifier, e.g. "Ctrl + c"
+    chords: BTreeMap<String, u64>,
+    // Chords counted under a name, e.g. Ctrl+c as copy, so the report shows what was done
+    aliases: Vec<(Chord, String)>,
+    // By alias name
+    actions: BTreeMap<String, u64>,
+    // Modifier presses by the side of the keyboard they're on, "left" or "right"
+    modifier_sides: BTreeMap<String, u64>,
+    // Chords by whether the modifiers are held with the same hand as the key, "same" or "cross"
+    chord_hands: BTreeMap<String, u64>,
+    last_write: Instant,
+}
+
+pub fn json_string(s: &str) -> String {
+    let mut ret = "\"".to_string();
+    for c in s.chars() {
+        match c {
+            '"' => ret.push_str("\\\""),
+            '\\' => ret.push_str("\\\\"),
+            c if c.is_control() => ret.push_str(&format!("\\u{:04x}", c as u32)),
+            c => ret.push(c),
+        }
+    }
+    ret.push('"');
+    ret
+}
+
+fn json_counts(counts: &BTreeMap<String, u64>) -> String {
+    let entries = counts
+        .iter()
+        .map(|(name, count)| format!("    {}: {count}", json_string(name)))
+        .collect::<Vec<_>>();
+    format!("{{\n{}\n  }}", entries.join(",\n"))
+}
+
+fn csv_field(s: &str) -> String {
+    if s.contains([',', '"', '\n']) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.to_string()
+    }
+}
+
+impl Stats {
+    pub fn new(
+        path: PathBuf,
+        session_code: Option<String>,
+        aliases: Vec<(Chord, String)>,
+    ) -> Stats {
+        Stats {
+            path,
+            session_code,
+            started: Instant::now(),
+            keys: BTreeMap::new(),
+            chords: BTreeMap::new(),
+            aliases,
+            actions: BTreeMap::new(),
+            modifier_sides: BTreeMap::new(),
+            chord_hands: BTreeMap::new(),
+            last_write: Instant::now(),
+        }
+    }
+
+    pub fn record(&mut self, key_s: &str, modifiers: &Modifiers) {
+        *self.keys.entry(key_s.to_string()).or_default() += 1;
+        if *modifiers != Modifiers::default() {
+            let chord = Symbols::plain().chord(modifiers, key_s);
+            *self.chords.entry(chord).or_default() += 1;
+        }
+
+        // Several chords can share a name, e.g. Ctrl+z and Ctrl+Shift+z both as history
+        let action = self
+            .aliases
+            .iter()
+            .find(|(chord, _)| chord.matches_key(key_s, modifiers));
+        if let Some((_, name)) = action {
+            *self.actions.entry(name.clone()).or_default() += 1;
+        }
+    }
+
+    pub fn record_modifier(&mut self, code: u16) {
+        if let Some(hand) = Hand::of(code) {
+            *self
+                .modifier_sides
+                .entry(hand.name().to_string())
+                .or_default() += 1;
+        }
+    }
+
+    // Same hand when every held modifier is on the key's side of the keyboard, e.g. left Ctrl+c
+    pub fn record_hands(&mut self, code: u16, modifier_codes: &[u16]) {
+        let hand = match Hand::of(code) {
+            Some(v) => v,
+            None => return,
+        };
+
+        let modifier_hands = modifier_codes
+            .iter()
+            .filter_map(|code| Hand::of(*code))
+            .collect::<Vec<_>>();
+        if modifier_hands.is_empty() {
+            return;
+        }
+
+        let kind = match modifier_hands.iter().all(|h| *h == hand) {
+            true => "same",
+            false => "cross",
+        };
+        *self.chord_hands.entry(kind.to_string()).or_default() += 1;
+    }
+
+    fn to_json(&self, timings: Option<&ChordTimings>) -> String {
+        let mut fields = vec![
+            format!(
+                "  \"duration_secs\": {:.1}",
+                self.started.elapsed().as_secs_f64()
+            ),
+            format!("  \"keys\": {}", json_counts(&self.keys)),
+            format!("  \"chords\": {}", json_counts(&self.chords)),
+        ];
+
+        if !self.aliases.is_empty() {
+            fields.push(format!("  \"actions\": {}", json_counts(&self.actions)));
+        }
+        fields.push(format!(
+            "  \"modifier_sides\": {}",
+            json_counts(&self.modifier_sides)
+        ));
+        fields.push(format!(
+            "  \"chord_hands\": {}",
+            json_counts(&self.chord_hands)
+        ));
+
+        if let Some(code) = &self.session_code {
+            fields.insert(0, format!("  \"session_code\": {}", json_string(code)));
+        }
+
+        if let Some(timings) = timings {
+            let slowest = timings
+                .slowest()
+                .into_iter()
+                .map(|(chord, uses, average)| {
+                    format!(
+                        "    {{ \"chord\": {}, \"uses\": {uses}, \"average_ms\": {} }}",
+                        json_string(chord),
+                        average.as_millis()
+                    )
+                })
+                .collect::<Vec<_>>();
+            fields.push(format!(
+                "  \"slowest_shortcuts\": [\n{}\n  ]",
+                slowest.join(",\n")
+            ));
+        }
+
+        format!("{{\n{}\n}}\n", fields.join(",\n"))
+    }
+
+    // One row per key and chord, the duration gets a row of its own. Slowest shortcuts have their
+    // average time in milliseconds as the count
+    fn to_csv(&self, timings: Option<&ChordTimings>) -> String {
+        let mut ret = "kind,name,count\n".to_string();
+        ret.push_str(&format!(
+            "session,duration_secs,{:.1}\n",
+            self.started.elapsed().as_secs_f64()
+        ));
+        if let Some(code) = &self.session_code {
+            ret.push_str(&format!("session,code,{}\n", csv_field(code)));
+        }
+        for (kind, counts) in [
+            ("key", &self.keys),
+            ("chord", &self.chords),
+            ("action", &self.actions),
+            ("modifier_side", &self.modifier_sides),
+            ("chord_hand", &self.chord_hands),
+        ] {
+            for (name, count) in counts {
+                ret.push_str(&format!("{kind},{},{count}\n", csv_field(name)));
+            }
+        }
+        for (chord, _, average) in timings.map(ChordTimings::slowest).unwrap_or_default() {
+            ret.push_str(&format!(
+                "slowest_shortcut_ms,{},{}\n",
+                csv_field(chord),
+                average.as_millis()
+            ));
+        }
+        ret
+    }
+
+    pub fn write(&mut self, timings: Option<&ChordTimings>) -> Result<(), StatsError> {
+        self.last_write = Instant::now();
+        let content = match self.path.extension().and_then(|e| e.to_str()) {
+            Some("csv") => self.to_csv(timings),
+            _ => self.to_json(timings),
+        };
+        fs::write(&self.path, content).map_err(StatsError::Write)
+    }
+
+    // How long until the next periodic write
+    pub fn next_write(&self) -> Duration {
+        STATS_INTERVAL.saturating_sub(self.last_write.elapsed())
+    }
+}
@@ -0,0 +1,146 @@
+use std::collections::HashMap;
+
+use super::Modifiers;
+
+// Used by --symbols. Keys are keysym names as shown in the history, modifiers use their history
+// names
+const GLYPHS: &[(&str, &str)] = &[
+    ("Ctrl", "⌃"),
+    ("Shift", "⇧"),
+    ("Alt", "⌥"),
+    ("Super", "⌘"),
+    ("Return", "⏎"),
+    ("KP_Enter", "⌤"),
+    ("Tab", "⇥"),
+    ("ISO_Left_Tab", "⇤"),
+    ("BackSpace", "⌫"),
+    ("Delete", "⌦"),
+    ("Escape", "⎋"),
+    ("space", "␣"),
+    ("Caps_Lock", "⇪"),
+    ("Left", "←"),
+    ("Right", "→"),
+    ("Up", "↑"),
+    ("Down", "↓"),
+    ("Home", "⇱"),
+    ("End", "⇲"),
+    ("Prior", "⇞"),
+    ("Next", "⇟"),
+];
+
+// What to show for modifiers and keys in the history
+pub struct Symbols {
+    table: HashMap<String, String>,
+    // Follows each modifier
+    separator: &'static str,
+}
+
+impl Symbols {
+    pub fn plain() -> Symbols {
+        Symbols {
+            table: HashMap::new(),
+            separator: " + ",
+        }
+    }
+
+    pub fn glyphs() -> Symbols {
+        let table = GLYPHS
+            .iter()
+            .map(|(name, glyph)| (name.to_string(), glyph.to_string()))
+            .collect();
+
+        Symbols {
+            table,
+            separator: " ",
+        }
+    }
+
+    pub fn insert(&mut self, name: String, display: String) {
+        self.table.insert(name, display);
+    }
+
+    pub fn get<'a>(&'a self, name: &'a str) -> &'a str {
+        self.table.get(name).map(String::as_str).unwrap_or(name)
+    }
+
+    pub fn chord(&self, modifiers: &Modifiers, key: &str) -> String {
+        self.chord_keys(modifiers, key).join(self.separator)
+    }
+
+    // Each modifier and the key on their own, e.g. for keycaps
+    pub fn chord_keys<'a>(&'a self, modifiers: &Modifiers, key: &'a str) -> Vec<&'a str> {
+        let mut ret = Vec::new();
+        for (held, name) in [
+            (modifiers.alt, "Alt"),
+            (modifiers.sup, "Super"),
+            (modifiers.ctrl, "Ctrl"),
+            (modifiers.shift, "Shift"),
+        ] {
+            if held {
+                ret.push(self.get(name));
+            }
+        }
+
+        ret.push(self.get(key));
+        ret
+    }
+}
+
+// name=display, e.g. Return=⏎
+pub fn parse_entry(s: &str) -> Option<(String, String)> {
+    let (name, display) = s.split_once('=')?;
+    if name.is_empty() {
+        return None;
+    }
+
+    Some((name.to_string(), display.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(name: &str, display: &str) -> Option<(String, String)> {
+        Some((name.to_string(), display.to_string()))
+    }
+
+    #[test]
+    fn parse_entry_splits_at_first_equals() {
+        assert_eq!(parse_entry("Return=⏎"), entry("Return", "⏎"));
+        assert_eq!(parse_entry("equal=a=b"), entry("equal", "a=b"));
+    }
+
+    #[test]
+    fn parse_entry_allows_empty_display() {
+        assert_eq!(parse_entry("Shift="), entry("Shift", ""));
+    }
+
+    #[test]
+    fn parse_entry_rejects_invalid() {
+        for s in ["", "Return", "=⏎", "=="] {
+            assert_eq!(parse_entry(s), None, "{s:?}");
+        }
+    }
+
+    #[test]
+    fn chord_with_overrides() {
+        let ctrl_shift = Modifiers {
+            ctrl: true,
+            shift: true,
+            ..Default::default()
+        };
+
+        let mut symbols = Symbols::plain();
+        assert_eq!(
+            symbols.chord(&ctrl_shift, "Return"),
+            "Ctrl + Shift + Return"
+        );
+        symbols.insert("Return".to_string(), "Enter".to_string());
+        assert_eq!(symbols.chord(&ctrl_shift, "Return"), "Ctrl + Shift + Enter");
+
+        let mut symbols = Symbols::glyphs();
+        assert_eq!(symbols.chord(&ctrl_shift, "Return"), "⌃ ⇧ ⏎");
+        symbols.insert("Ctrl".to_string(), "C".to_string());
+        assert_eq!(symbols.chord(&ctrl_shift, "a"), "C ⇧ a");
+    }
+}
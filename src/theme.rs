@@ -0,0 +1,139 @@
+use eframe::egui::{self, Color32, FontId, RichText, Rounding, Sense, Stroke, Vec2};
+
+// Colors of the overlay. Presets are picked with --theme, single colors can be overridden after
+pub struct Theme {
+    pub text: Color32,
+    pub background: Color32,
+    // Background opacity when the window is transparent
+    pub background_alpha: u8,
+    // Drawn behind each history line
+    pub pill: Option<Color32>,
+    pub border: Option<Color32>,
+    // Drawn under text, keeps it readable without a background
+    pub shadow: Option<Color32>,
+}
+
+impl Theme {
+    pub fn preset(name: &str) -> Option<Theme> {
+        let theme = match name {
+            "dark" => Theme {
+                text: Color32::WHITE,
+                background: Color32::BLACK,
+                background_alpha: 127,
+                pill: None,
+                border: None,
+                shadow: None,
+            },
+            "light" => Theme {
+                text: Color32::from_rgb(30, 30, 36),
+                background: Color32::from_rgb(245, 245, 245),
+                background_alpha: 200,
+                pill: Some(Color32::WHITE),
+                border: Some(Color32::from_rgb(200, 200, 208)),
+                shadow: None,
+            },
+            "transparent" => Theme {
+                text: Color32::WHITE,
+                background: Color32::BLACK,
+                background_alpha: 0,
+                pill: None,
+                border: None,
+                shadow: Some(Color32::from_black_alpha(200)),
+            },
+            "high-contrast" => Theme {
+                text: Color32::YELLOW,
+                background: Color32::BLACK,
+                background_alpha: 255,
+                pill: Some(Color32::BLACK),
+                border: Some(Color32::WHITE),
+                shadow: None,
+            },
+            _ => return None,
+        };
+
+        Some(theme)
+    }
+
+    pub fn panel_fill(&self, opaque: bool) -> Color32 {
+        let alpha = match opaque {
+            true => 255,
+            false => self.background_alpha,
+        };
+        let [r, g, b, _] = self.background.to_array();
+        Color32::from_rgba_unmultiplied(r, g, b, alpha)
+    }
+
+    // A history line, alpha fades everything drawn for it
+    pub fn label(&self, ui: &mut egui::Ui, text: RichText, font_size: f32, alpha: f32) {
+        let frame = egui::Frame::none()
+            .fill(
+                self.pill
+                    .unwrap_or(Color32::TRANSPARENT)
+                    .gamma_multiply(alpha),
+            )
+            .stroke(match self.border {
+                Some(v) => Stroke::new(1.0, v.gamma_multiply(alpha)),
+                None => Stroke::NONE,
+            })
+            .rounding(font_size / 3.0);
+        let frame = match self.pill.or(self.border) {
+            Some(_) => {
+                frame.inner_margin(egui::Margin::symmetric(font_size / 2.0, font_size / 6.0))
+            }
+            None => frame,
+        };
+
+        frame.show(ui, |ui| {
+            let (pos, galley, _) = egui::Label::new(text).layout_in_ui(ui);
+            if let Some(shadow) = self.shadow {
+                let offset = egui::Vec2::splat((font_size / 12.0).max(1.0));
+                galley.clone().paint_with_color_override(
+                    ui.painter(),
+                    pos + offset,
+                    shadow.gamma_multiply(alpha),
+                );
+            }
+            galley.paint_with_fallback_color(ui.painter(), pos, self.text);
+        });
+    }
+
+    // A single key drawn like a physical keycap, e.g. <kbd> on a web page. Uses the pill and border
+    // colors, or greys if the theme has none
+    pub fn keycap(
+        &self,
+        ui: &mut egui::Ui,
+        key: &str,
+        font_id: FontId,
+        color: Color32,
+        alpha: f32,
+    ) {
+        let font_size = font_id.size;
+        let fill = self.pill.unwrap_or(Color32::from_gray(50));
+        let border = self.border.unwrap_or(Color32::from_gray(130));
+        // The darker edge along the bottom of the cap
+        let depth = (font_size / 8.0).max(1.0);
+        let padding = Vec2::new(font_size / 2.5, font_size / 6.0);
+        let rounding = Rounding::same(font_size / 4.0);
+
+        let galley = ui.painter().layout_no_wrap(key.to_string(), font_id, color);
+        let size = galley.size() + padding * 2.0 + Vec2::new(0.0, depth);
+        // Single letters get square caps
+        let size = Vec2::new(size.x.max(size.y - depth), size.y);
+        let (rect, _) = ui.allocate_exact_size(size, Sense::hover());
+        let face = rect.with_max_y(rect.max.y - depth);
+
+        let painter = ui.painter();
+        painter.rect_filled(
+            face.translate(Vec2::new(0.0, depth)),
+            rounding,
+            border.gamma_multiply(0.6 * alpha),
+        );
+        painter.rect(
+            face,
+            rounding,
+            fill.gamma_multiply(alpha),
+            Stroke::new(1.0, border.gamma_multiply(alpha)),
+        );
+        painter.galley(face.center() - galley.size() / 2.0, galley);
+    }
+}
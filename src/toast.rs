@@ -0,0 +1,106 @@
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+use eframe::egui::{self, Align2, Color32, FontFamily, RichText};
+
+const MAX_TOASTS: usize = 5;
+
+#[derive(Clone, Copy)]
+pub enum ToastLevel {
+    Info,
+    Error,
+}
+
+impl ToastLevel {
+    fn color(&self) -> Color32 {
+        match self {
+            ToastLevel::Info => Color32::from_rgb(160, 210, 255),
+            ToastLevel::Error => Color32::from_rgb(255, 120, 120),
+        }
+    }
+}
+
+// Status message from any subsystem, shown in the overlay since nobody watches the terminal
+pub struct Toast {
+    level: ToastLevel,
+    text: String,
+}
+
+impl Toast {
+    pub fn info(text: impl Into<String>) -> Toast {
+        Toast {
+            level: ToastLevel::Info,
+            text: text.into(),
+        }
+    }
+
+    pub fn error(text: impl Into<String>) -> Toast {
+        Toast {
+            level: ToastLevel::Error,
+            text: text.into(),
+        }
+    }
+}
+
+pub struct Toasts {
+    duration: Duration,
+    // Stamped with the app's clock the first time they're expired
+    items: VecDeque<(Toast, Option<Instant>)>,
+}
+
+impl Toasts {
+    pub fn new(duration: Duration) -> Toasts {
+        Toasts {
+            duration,
+            items: VecDeque::new(),
+        }
+    }
+
+    pub fn push(&mut self, toast: Toast) {
+        self.items.push_back((toast, None));
+        while self.items.len() > MAX_TOASTS {
+            self.items.pop_front();
+        }
+    }
+
+    pub fn expire(&mut self, ctx: &egui::Context, now: Instant) {
+        let duration = self.duration;
+        for (_, shown_at) in &mut self.items {
+            shown_at.get_or_insert(now);
+        }
+        let shown_for =
+            |shown_at: &Option<Instant>| now.saturating_duration_since(shown_at.unwrap_or(now));
+        self.items
+            .retain(|(_, shown_at)| shown_for(shown_at) < duration);
+
+        // Oldest toast goes first
+        if let Some((_, shown_at)) = self.items.front() {
+            ctx.request_repaint_after(duration.saturating_sub(shown_for(shown_at)));
+        }
+    }
+
+    pub fn render(&self, ctx: &egui::Context) {
+        if self.items.is_empty() {
+            return;
+        }
+
+        egui::Area::new("toasts")
+            .anchor(Align2::CENTER_TOP, [0.0, 8.0])
+            .show(ctx, |ui| {
+                for (toast, _) in &self.items {
+                    let label_text = RichText::new(&toast.text)
+                        .family(FontFamily::Monospace)
+                        .color(toast.level.color())
+                        .size(15.0);
+
+                    egui::Frame::none()
+                        .fill(Color32::from_black_alpha(200))
+                        .rounding(4.0)
+                        .inner_margin(6.0)
+                        .show(ui, |ui| ui.label(label_text));
+                }
+            });
+    }
+}
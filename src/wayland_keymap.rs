@@ -0,0 +1,92 @@
+use std::{
+    fs::File,
+    io::Error as IoError,
+    os::unix::{fs::FileExt, io::FromRawFd},
+    sync::mpsc::{Receiver, Sender},
+    thread,
+};
+
+use eframe::egui;
+use wayland_client::{
+    protocol::{wl_keyboard, wl_seat::WlSeat},
+    ConnectError, Display, GlobalError, GlobalManager,
+};
+
+use super::{ipc::IpcCommand, toast::Toast, xkbcommon::KeymapSource};
+
+#[derive(Debug)]
+pub enum WaylandKeymapError {
+    Connect(ConnectError),
+    Roundtrip(IoError),
+    NoSeat(GlobalError),
+}
+
+// Follows the keymap the compositor hands to its clients. The compositor sends it as soon as we
+// ask for a keyboard and again whenever it's replaced, e.g. after a layout change in the
+// compositor config
+pub fn spawn_listener(
+    tx: Sender<IpcCommand>,
+    toast_tx: Sender<Toast>,
+    rx: Receiver<egui::Context>,
+) -> Result<(), WaylandKeymapError> {
+    let display = Display::connect_to_env().map_err(WaylandKeymapError::Connect)?;
+
+    thread::spawn(move || {
+        let ctx = rx.recv().unwrap();
+        if let Err(e) = listener_thread(display, tx, ctx.clone()) {
+            let _ = toast_tx.send(Toast::error(format!("Lost wayland keymap: {e:?}")));
+            ctx.request_repaint();
+        }
+    });
+
+    Ok(())
+}
+
+fn listener_thread(
+    display: Display,
+    tx: Sender<IpcCommand>,
+    ctx: egui::Context,
+) -> Result<(), WaylandKeymapError> {
+    let mut event_queue = display.create_event_queue();
+    let attached_display = display.attach(event_queue.token());
+    let globals = GlobalManager::new(&attached_display);
+    event_queue
+        .sync_roundtrip(&mut (), |_, _, _| ())
+        .map_err(WaylandKeymapError::Roundtrip)?;
+
+    let seat = globals
+        .instantiate_exact::<WlSeat>(1)
+        .map_err(WaylandKeymapError::NoSeat)?;
+    let keyboard = seat.get_keyboard();
+    keyboard.quick_assign(move |_, event, _| {
+        let (format, fd, size) = match event {
+            wl_keyboard::Event::Keymap { format, fd, size } => (format, fd, size),
+            _ => return,
+        };
+
+        let f = unsafe { File::from_raw_fd(fd) };
+        if format != wl_keyboard::KeymapFormat::XkbV1 {
+            return;
+        }
+
+        // The fd may be shared with other clients, so don't move its offset
+        let mut buf = vec![0; size as usize];
+        if f.read_exact_at(&mut buf, 0).is_err() {
+            return;
+        }
+
+        // The keymap string is nul terminated
+        while buf.last() == Some(&0) {
+            buf.pop();
+        }
+
+        let _ = tx.send(IpcCommand::SetKeymap(KeymapSource::Buffer(buf)));
+        ctx.request_repaint();
+    });
+
+    loop {
+        event_queue
+            .dispatch(&mut (), |_, _, _| ())
+            .map_err(WaylandKeymapError::Roundtrip)?;
+    }
+}
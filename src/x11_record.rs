@@ -0,0 +1,137 @@
+use std::{
+    sync::mpsc::{Receiver, Sender},
+    thread,
+};
+
+use eframe::egui;
+use x11rb::{
+    connection::{Connection, RequestConnection},
+    errors::{ConnectError, ConnectionError, ReplyError, ReplyOrIdError},
+    protocol::{
+        record::{self, ConnectionExt as _},
+        xproto,
+    },
+    rust_connection::RustConnection,
+};
+
+use super::{input_bindings, now_timestamp, toast::Toast, EventBatch, InputEvent};
+
+#[derive(Debug)]
+pub enum X11Error {
+    Connect(ConnectError),
+    Connection(ConnectionError),
+    MissingRecordExtension,
+    CreateContext(ReplyOrIdError),
+    Reply(ReplyError),
+}
+
+// X keycodes are evdev codes offset by 8
+const EVDEV_OFFSET: u8 = 8;
+
+// from recordproto.h
+const RECORD_FROM_SERVER: u8 = 0;
+
+// Listens to key events for all clients through the XRecord extension, so no access to
+// /dev/input is needed
+pub fn spawn_recorder(
+    tx: Sender<EventBatch>,
+    toast_tx: Sender<Toast>,
+    rx: Receiver<egui::Context>,
+) -> Result<(), X11Error> {
+    // The recording connection is busy streaming events once enabled, so the context has to be
+    // set up on a second one. The context only lives as long as the connection that created it
+    let (ctrl_conn, _) = x11rb::connect(None).map_err(X11Error::Connect)?;
+    let (data_conn, _) = x11rb::connect(None).map_err(X11Error::Connect)?;
+
+    ctrl_conn
+        .extension_information(record::X11_EXTENSION_NAME)
+        .map_err(X11Error::Connection)?
+        .ok_or(X11Error::MissingRecordExtension)?;
+
+    let context = ctrl_conn.generate_id().map_err(X11Error::CreateContext)?;
+    let range = record::Range {
+        device_events: record::Range8 {
+            first: xproto::KEY_PRESS_EVENT,
+            last: xproto::KEY_RELEASE_EVENT,
+        },
+        ..Default::default()
+    };
+    ctrl_conn
+        .record_create_context(context, 0, &[record::CS::ALL_CLIENTS.into()], &[range])
+        .map_err(X11Error::Connection)?
+        .check()
+        .map_err(X11Error::Reply)?;
+
+    thread::spawn(move || recorder_thread(tx, toast_tx, rx, ctrl_conn, data_conn, context));
+    Ok(())
+}
+
+fn recorder_thread(
+    tx: Sender<EventBatch>,
+    toast_tx: Sender<Toast>,
+    rx: Receiver<egui::Context>,
+    // Kept alive for the context
+    _ctrl_conn: RustConnection,
+    data_conn: RustConnection,
+    context: record::Context,
+) {
+    let ctx = rx.recv().unwrap();
+
+    let replies = match data_conn.record_enable_context(context) {
+        Ok(v) => v,
+        Err(e) => {
+            let _ = toast_tx.send(Toast::error(format!("Failed to enable XRecord: {e}")));
+            ctx.request_repaint();
+            return;
+        }
+    };
+
+    for reply in replies {
+        let reply = match reply {
+            Ok(v) => v,
+            Err(e) => {
+                let _ = toast_tx.send(Toast::error(format!("Lost X11 connection: {e}")));
+                ctx.request_repaint();
+                return;
+            }
+        };
+
+        if reply.category != RECORD_FROM_SERVER {
+            continue;
+        }
+
+        // With no element header the data is just a run of 32 byte wire events. X reports key
+        // repeat as release/press pairs, so held keys show up as repeated presses. X has no
+        // SYN_REPORT, so everything in one reply is treated as one batch
+        let mut batch = Vec::new();
+        for wire_event in reply.data.chunks_exact(32) {
+            let value = match wire_event[0] & 0x7f {
+                xproto::KEY_PRESS_EVENT => 1,
+                xproto::KEY_RELEASE_EVENT => 0,
+                _ => continue,
+            };
+
+            let now = now_timestamp();
+            let event = input_bindings::input_event {
+                time: input_bindings::timeval {
+                    tv_sec: now.as_secs() as _,
+                    tv_usec: now.subsec_micros() as _,
+                },
+                type_: input_bindings::EV_KEY as u16,
+                code: wire_event[1].saturating_sub(EVDEV_OFFSET) as u16,
+                value,
+            };
+
+            batch.push(InputEvent { event });
+        }
+
+        if batch.is_empty() {
+            continue;
+        }
+
+        if tx.send(batch).is_err() {
+            return;
+        }
+        ctx.request_repaint();
+    }
+}
@@ -1,8 +1,11 @@
 use std::{
     env,
+    ffi::CString,
     fs::File,
     io::{BufReader, Error as IoError, Read},
+    path::{Path, PathBuf},
     process::{Command, Stdio},
+    ptr,
 };
 
 use super::{KeyPress, KeyPressState};
@@ -20,6 +23,64 @@ pub enum XkbCreationError {
     NoDisplay,
     RunXkbComp(IoError),
     XkbCompFail,
+    InvalidRuleNames,
+}
+
+// Rules, model, layout, variant and options to build a keymap from. Unset names fall back to the
+// xkbcommon defaults
+#[derive(Clone, Debug, Default)]
+pub struct RuleNames {
+    pub rules: Option<String>,
+    pub model: Option<String>,
+    pub layout: Option<String>,
+    pub variant: Option<String>,
+    pub options: Option<String>,
+}
+
+impl RuleNames {
+    // e.g. "layout=de variant=nodeadkeys"
+    pub fn parse(s: &str) -> Option<RuleNames> {
+        let mut ret = RuleNames::default();
+        for item in s.split_whitespace() {
+            let (key, val) = item.split_once('=')?;
+            let val = Some(val.to_string());
+            match key {
+                "rules" => ret.rules = val,
+                "model" => ret.model = val,
+                "layout" => ret.layout = val,
+                "variant" => ret.variant = val,
+                "options" => ret.options = val,
+                _ => return None,
+            }
+        }
+
+        Some(ret)
+    }
+}
+
+#[derive(Clone, Debug)]
+pub enum KeymapSource {
+    // A keymap file, e.g. from xkbcomp $DISPLAY out.xkb
+    File(PathBuf),
+    Names(RuleNames),
+    // Keymap text, e.g. as sent by a wayland compositor
+    Buffer(Vec<u8>),
+}
+
+impl KeymapSource {
+    pub fn parse(s: &str) -> Option<KeymapSource> {
+        let s = s.trim();
+        if s.is_empty() {
+            return None;
+        }
+
+        let ret = match RuleNames::parse(s) {
+            Some(names) => KeymapSource::Names(names),
+            None => KeymapSource::File(s.into()),
+        };
+
+        Some(ret)
+    }
 }
 
 macro_rules! xkb_ptr_wrapper {
@@ -74,6 +135,31 @@ impl Xkb {
         }
     }
 
+    pub fn from_source(source: &KeymapSource) -> Result<Xkb, XkbCreationError> {
+        unsafe {
+            let mut context = create_context()?;
+            let mut keymap = match source {
+                KeymapSource::File(path) => create_keymap_from_file(&mut context, path)?,
+                KeymapSource::Names(names) => create_keymap_from_names(&mut context, names)?,
+                KeymapSource::Buffer(buf) => create_keymap_from_buffer(&mut context, buf)?,
+            };
+            let state = create_state(&mut keymap)?;
+
+            Ok(Xkb { state })
+        }
+    }
+
+    // Forgets all held keys, for when events were skipped
+    pub fn reset_state(&mut self) -> Result<(), XkbCreationError> {
+        unsafe {
+            let keymap = bindings::xkb_state_get_keymap(self.state.as_ptr());
+            self.state = State::new(bindings::xkb_state_new(keymap))
+                .ok_or(XkbCreationError::StateCreationFailed)?;
+        }
+
+        Ok(())
+    }
+
     pub fn push_keycode(&mut self, keycode: u16, press_state: &KeyPressState) -> Option<KeyPress> {
         let xkb_code = evdev_code_to_xkb_code(keycode);
 
@@ -95,7 +181,21 @@ unsafe fn create_context() -> Result<Context, XkbCreationError> {
 
 unsafe fn create_keymap(context: &mut Context) -> Result<KeyMap, XkbCreationError> {
     let mapping_str = get_mappings_from_environment()?;
+    create_keymap_from_buffer(context, &mapping_str)
+}
 
+unsafe fn create_keymap_from_file(
+    context: &mut Context,
+    path: &Path,
+) -> Result<KeyMap, XkbCreationError> {
+    let mapping_str = read_mappings(path)?;
+    create_keymap_from_buffer(context, &mapping_str)
+}
+
+unsafe fn create_keymap_from_buffer(
+    context: &mut Context,
+    mapping_str: &[u8],
+) -> Result<KeyMap, XkbCreationError> {
     KeyMap::new(bindings::xkb_keymap_new_from_buffer(
         context.as_ptr(),
         mapping_str.as_ptr() as *const i8,
@@ -106,6 +206,40 @@ unsafe fn create_keymap(context: &mut Context) -> Result<KeyMap, XkbCreationErro
     .ok_or(XkbCreationError::KeymapCreationFailed)
 }
 
+unsafe fn create_keymap_from_names(
+    context: &mut Context,
+    names: &RuleNames,
+) -> Result<KeyMap, XkbCreationError> {
+    let to_cstring = |s: &Option<String>| {
+        s.as_deref()
+            .map(CString::new)
+            .transpose()
+            .map_err(|_| XkbCreationError::InvalidRuleNames)
+    };
+    let rules = to_cstring(&names.rules)?;
+    let model = to_cstring(&names.model)?;
+    let layout = to_cstring(&names.layout)?;
+    let variant = to_cstring(&names.variant)?;
+    let options = to_cstring(&names.options)?;
+
+    // NULL fields are filled in from the environment/system defaults by xkbcommon
+    let as_ptr = |s: &Option<CString>| s.as_ref().map(|s| s.as_ptr()).unwrap_or(ptr::null());
+    let rule_names = bindings::xkb_rule_names {
+        rules: as_ptr(&rules),
+        model: as_ptr(&model),
+        layout: as_ptr(&layout),
+        variant: as_ptr(&variant),
+        options: as_ptr(&options),
+    };
+
+    KeyMap::new(bindings::xkb_keymap_new_from_names(
+        context.as_ptr(),
+        &rule_names,
+        bindings::xkb_keymap_compile_flags_XKB_KEYMAP_COMPILE_NO_FLAGS,
+    ))
+    .ok_or(XkbCreationError::KeymapCreationFailed)
+}
+
 unsafe fn create_state(keymap: &mut KeyMap) -> Result<State, XkbCreationError> {
     State::new(bindings::xkb_state_new(keymap.as_ptr()))
         .ok_or(XkbCreationError::StateCreationFailed)
@@ -206,8 +340,11 @@ fn get_mappings_from_environment() -> Result<Vec<u8>, XkbCreationError> {
         return Err(XkbCreationError::XkbCompFail);
     }
 
-    let mut f =
-        BufReader::new(File::open(xkb_mapping_path).map_err(XkbCreationError::OpenMappings)?);
+    read_mappings(&xkb_mapping_path)
+}
+
+fn read_mappings(path: &Path) -> Result<Vec<u8>, XkbCreationError> {
+    let mut f = BufReader::new(File::open(path).map_err(XkbCreationError::OpenMappings)?);
 
     let mut mapping_str = Vec::new();
     f.read_to_end(&mut mapping_str)